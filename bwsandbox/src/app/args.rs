@@ -1,22 +1,90 @@
-use crate::{error::AppError, utils};
+use crate::{
+    app::{auto, dry_run, overrides::Override, search_path},
+    error::AppError,
+};
 use lexopt::Parser;
-use std::{ffi::OsString, path::PathBuf};
+use std::{
+    ffi::{OsStr, OsString},
+    path::PathBuf,
+};
 
-fn get_config_dir() -> Result<String, AppError> {
-    const CONFIG_DIR: &str = "XDG_CONFIG_HOME";
-    const HOME: &str = "HOME";
+#[derive(Debug)]
+pub enum Cli {
+    Run(Args),
+    Check(CheckArgs),
+    ListProfiles,
+    // Sandbox id with target state
+    Freeze(String, bool),
+    Ps,
+    Stop(String),
+    // Sandbox id with command to run inside
+    Enter(String, Vec<OsString>),
+}
 
-    match std::env::var(CONFIG_DIR) {
-        Ok(v) if !v.is_empty() => Ok(v),
-        Err(err @ std::env::VarError::NotUnicode(_)) => {
-            //
-            Err(AppError::Env(CONFIG_DIR.into(), err))
+impl Cli {
+    pub fn from_iter(iter: impl Iterator<Item = OsString>) -> Result<Self, AppError> {
+        // Subcommand allowed only as first arg, so `-- check` is still an app name
+        let args = iter.collect::<Vec<_>>();
+        match args.get(1).and_then(|v| v.to_str()) {
+            Some("check") => CheckArgs::from_iter(args.into_iter().skip(1)).map(Self::Check),
+            Some("list-profiles") if args.len() == 2 => Ok(Self::ListProfiles),
+            Some("ps") if args.len() == 2 => Ok(Self::Ps),
+            Some("list-profiles" | "ps") => Err(AppError::BadArgs),
+            Some("stop") => match &args[2..] {
+                [id] => Ok(Self::Stop(id.to_string_lossy().into_owned())),
+                _ => Err(AppError::BadArgs),
+            },
+            Some("enter") => match &args[2..] {
+                [id, command @ ..] => {
+                    let command = command.strip_prefix(&["--".into()]).unwrap_or(command);
+                    if command.is_empty() {
+                        return Err(AppError::BadArgs);
+                    }
+                    Ok(Self::Enter(
+                        id.to_string_lossy().into_owned(),
+                        command.to_vec(),
+                    ))
+                }
+                _ => Err(AppError::BadArgs),
+            },
+            Some(cmd @ ("freeze" | "thaw")) => {
+                let frozen = cmd == "freeze";
+                match &args[2..] {
+                    [id] => Ok(Self::Freeze(id.to_string_lossy().into_owned(), frozen)),
+                    _ => Err(AppError::BadArgs),
+                }
+            }
+            _ => Args::from_iter(args.into_iter()).map(Self::Run),
         }
-        // Handle both scenarios: NotPreseted and presented but empty
-        _ => {
-            let home = std::env::var(HOME).map_err(AppError::env(HOME))?;
-            Ok(format!("{home}/.config"))
+    }
+}
+
+#[derive(Debug)]
+pub struct CheckArgs {
+    pub config_dir: PathBuf,
+    pub config: String,
+}
+
+impl CheckArgs {
+    pub fn from_iter(iter: impl Iterator<Item = OsString>) -> Result<Self, AppError> {
+        use lexopt::prelude::{Long, Short};
+
+        let mut config: Option<(PathBuf, String)> = None;
+
+        let mut parser = Parser::from_iter(iter);
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Short('f') | Long("config-file") => config = Some(parse_file(&mut parser)?),
+                Short('n') | Long("config-name") => config = Some(parse_name(&mut parser)?),
+                _ => return Err(arg.unexpected().into()),
+            }
         }
+
+        let (config_file, config) = config.ok_or(AppError::BadArgs)?;
+        Ok(Self {
+            config_dir: config_file.parent().expect("Missing config home?").into(),
+            config,
+        })
     }
 }
 
@@ -25,8 +93,13 @@ pub struct Args {
     pub app: OsString,
     #[allow(clippy::struct_field_names)]
     pub app_args: Vec<OsString>,
+    pub config_file: PathBuf,
     pub config_dir: PathBuf,
     pub config: String,
+    pub dry_run: Option<dry_run::Format>,
+    pub exit_report: Option<PathBuf>,
+    pub overrides: Vec<Override>,
+    pub pty: bool,
 }
 
 impl Args {
@@ -35,6 +108,10 @@ impl Args {
 
         let mut config: Option<(PathBuf, String)> = None;
         let mut config_auto = false;
+        let mut dry_run = None;
+        let mut exit_report = None;
+        let mut overrides = Vec::new();
+        let mut pty = false;
         let mut rest = Vec::new();
 
         let mut parser = Parser::from_iter(iter);
@@ -43,6 +120,14 @@ impl Args {
                 Short('f') | Long("config-file") => config = Some(parse_file(&mut parser)?),
                 Short('n') | Long("config-name") => config = Some(parse_name(&mut parser)?),
                 Short('a') | Long("config-auto") => config_auto = true,
+                Long("dry-run") => {
+                    let format = parser.optional_value();
+                    dry_run = Some(dry_run::Format::from_arg(format.as_deref())?);
+                }
+                Long("exit-report") => exit_report = Some(parser.value()?.into()),
+                Long("set") => overrides.push(Override::context(parser.value()?)?),
+                Long("set-service") => overrides.push(Override::service(parser.value()?)?),
+                Long("pty") => pty = true,
                 Value(v) => rest.push(v),
                 _ => return Err(arg.unexpected().into()),
             }
@@ -58,7 +143,7 @@ impl Args {
 
         let app_name = rest.remove(0);
         if config_auto {
-            config = Some(from_auto(app_name.clone())?);
+            config = Some(from_auto(&app_name, &rest)?);
         }
 
         let (config_file, config) = config.expect("Config must be ready");
@@ -66,7 +151,12 @@ impl Args {
             app: app_name,
             app_args: rest,
             config_dir: config_file.parent().expect("Missing config home?").into(),
+            config_file,
             config,
+            dry_run,
+            exit_report,
+            overrides,
+            pty,
         })
     }
 }
@@ -84,17 +174,16 @@ fn parse_name(parser: &mut Parser) -> Result<(PathBuf, String), AppError> {
     let mut name = parser.value()?;
     name.push(".toml");
 
-    from_name(name)
+    from_name(&name)
 }
 
-fn from_name(name: OsString) -> Result<(PathBuf, String), AppError> {
-    let config_dir = get_config_dir()?;
-    let config_path = PathBuf::from(&config_dir).join(utils::APP_NAME).join(name);
+pub fn from_name(name: &OsStr) -> Result<(PathBuf, String), AppError> {
+    let config_path = search_path::find(name)?;
     let content = std::fs::read_to_string(&config_path).map_err(AppError::file(&config_path))?;
     Ok((config_path, content))
 }
 
-fn from_auto(mut app_name: OsString) -> Result<(PathBuf, String), AppError> {
-    app_name.push(".toml");
-    from_name(app_name)
+fn from_auto(app: &OsStr, app_args: &[OsString]) -> Result<(PathBuf, String), AppError> {
+    let name = auto::profile_name(app, app_args)?;
+    from_name(&auto::profile_file(&name))
 }
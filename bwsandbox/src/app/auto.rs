@@ -0,0 +1,218 @@
+use crate::{app::search_path, error::AppError, utils};
+use regex_lite::Regex;
+use serde::Deserialize;
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+};
+
+pub const AUTO_FILE: &str = "auto.toml";
+
+// Rules checked in order, the first rule with all set matchers passed wins
+// Without any match profile named as app basename used, then `default`
+#[derive(Debug, Deserialize)]
+struct AutoConfig {
+    default: Option<String>,
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Rule {
+    profile: String,
+    // Exact app name as launched, without dirs
+    basename: Option<String>,
+    // Matched over resolved app path, `*` stops on `/`, `**` does not
+    glob: Option<Glob>,
+    // Matched over resolved app path
+    regex: Option<Pattern>,
+    // Matched over every app arg, at least one must match
+    args: Option<Pattern>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "String")]
+struct Pattern(Regex);
+
+impl TryFrom<String> for Pattern {
+    type Error = regex_lite::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Regex::new(&value).map(Self)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "String")]
+struct Glob(Regex);
+
+impl TryFrom<String> for Glob {
+    type Error = regex_lite::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let mut regex = String::from("^");
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    regex.push_str(".*");
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                c => regex.push_str(&regex_lite::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        regex.push('$');
+        Regex::new(&regex).map(Self)
+    }
+}
+
+struct AppInfo<'a> {
+    basename: &'a OsStr,
+    path: PathBuf,
+    args: &'a [OsString],
+}
+
+impl Rule {
+    fn matches(&self, app: &AppInfo) -> bool {
+        let path = app.path.to_string_lossy();
+        let basename = self
+            .basename
+            .as_ref()
+            .is_none_or(|v| app.basename == v.as_str());
+        let glob = self.glob.as_ref().is_none_or(|v| v.0.is_match(&path));
+        let regex = self.regex.as_ref().is_none_or(|v| v.0.is_match(&path));
+        let args = self.args.as_ref().is_none_or(|v| {
+            let mut args = app.args.iter().map(|v| v.to_string_lossy());
+            args.any(|arg| v.0.is_match(&arg))
+        });
+        basename && glob && regex && args
+    }
+}
+
+impl AutoConfig {
+    fn load() -> Result<Option<Self>, AppError> {
+        let Some(path) = search_path::find_first(AUTO_FILE.as_ref())? else {
+            return Ok(None);
+        };
+        log::trace!("Auto profile rules: {}", path.display());
+        let content = std::fs::read_to_string(&path).map_err(AppError::file(&path))?;
+        let config = utils::deserialize(&content)?;
+        Ok(Some(config))
+    }
+
+    fn select(&self, app: &AppInfo) -> Result<OsString, AppError> {
+        if let Some(rule) = self.rules.iter().find(|v| v.matches(app)) {
+            return Ok(rule.profile.as_str().into());
+        }
+
+        let own_exists = search_path::find_first(&profile_file(app.basename))?.is_some();
+        match &self.default {
+            Some(default) if !own_exists => Ok(default.into()),
+            _ => Ok(app.basename.into()),
+        }
+    }
+}
+
+pub fn profile_file(name: &OsStr) -> OsString {
+    let mut file = name.to_owned();
+    file.push(".toml");
+    file
+}
+
+// Resolve symlinks and $PATH lookups, so rules can match real binary location
+fn resolve_app(app: &OsStr) -> PathBuf {
+    let path = Path::new(app);
+    let found = if path.components().count() > 1 {
+        Ok(path.to_owned())
+    } else {
+        app.to_str()
+            .ok_or_else(|| AppError::file(path)(std::io::ErrorKind::InvalidInput.into()))
+            .and_then(utils::which_bin)
+    };
+
+    match found.and_then(|v| v.canonicalize().map_err(AppError::file(v))) {
+        Ok(v) => v,
+        Err(e) => {
+            log::debug!("Failed to resolve app path: {e:?}");
+            path.to_owned()
+        }
+    }
+}
+
+pub fn profile_name(app: &OsStr, args: &[OsString]) -> Result<OsString, AppError> {
+    let basename = Path::new(app).file_name().unwrap_or(app);
+    let Some(config) = AutoConfig::load()? else {
+        return Ok(basename.into());
+    };
+
+    let app = AppInfo {
+        basename,
+        path: resolve_app(app),
+        args,
+    };
+    let name = config.select(&app)?;
+    log::info!("Auto selected profile: {}", name.display());
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app<'a>(path: &'a str, args: &'a [OsString]) -> AppInfo<'a> {
+        AppInfo {
+            basename: Path::new(path).file_name().unwrap(),
+            path: path.into(),
+            args,
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        let config: AutoConfig = toml::from_str(
+            r#"
+            default = "generic"
+
+            [[rules]]
+            profile = "browser-private"
+            regex = "^/usr/bin/(firefox|chromium)$"
+            args = "^--(private-window|incognito)$"
+
+            [[rules]]
+            profile = "browser"
+            regex = "^/usr/bin/(firefox|chromium)$"
+
+            [[rules]]
+            profile = "games"
+            glob = "/opt/games/**"
+
+            [[rules]]
+            profile = "steam"
+            basename = "steam"
+            "#,
+        )
+        .unwrap();
+
+        let private = [OsString::from("--incognito")];
+        let cases = [
+            (app("/usr/bin/chromium", &private), "browser-private"),
+            (app("/usr/bin/firefox", &[]), "browser"),
+            (app("/opt/games/doom/bin/doom", &[]), "games"),
+            (app("/usr/bin/steam", &[]), "steam"),
+            (app("/usr/bin/no-such-app-for-sure", &[]), "generic"),
+        ];
+        for (app, expected) in cases {
+            assert_eq!(config.select(&app).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_glob() {
+        let glob = Glob::try_from("/opt/*/bin/app-?.sh".to_owned()).unwrap();
+        assert!(glob.0.is_match("/opt/game/bin/app-1.sh"));
+        assert!(!glob.0.is_match("/opt/game/nested/bin/app-1.sh"));
+        assert!(!glob.0.is_match("/opt/game/bin/app-1xsh"));
+    }
+}
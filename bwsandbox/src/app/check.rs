@@ -0,0 +1,114 @@
+use crate::{
+    app::{
+        config::SINGLE_INSTANCE_KEY,
+        hooks::{HOOKS_KEY, Hooks},
+        lifecycle::{LIFECYCLE_KEY, Lifecycle},
+        output::{OUTPUT_KEY, Output},
+        profile,
+    },
+    config::BwrapCmd,
+    error::AppError,
+    services::ServicesConfig,
+    utils,
+};
+use serde::Deserialize;
+use std::path::Path;
+
+const BWRAP_SECTION: &str = "bwrap";
+
+#[derive(Debug)]
+pub struct Problem {
+    pub section: String,
+    pub error: AppError,
+}
+
+impl Problem {
+    fn new(section: impl Into<String>, error: AppError) -> Self {
+        Self {
+            section: section.into(),
+            error,
+        }
+    }
+}
+
+// Each section deserialized separately to report every broken section, not only the first one
+pub fn check_profile(dir: &Path, content: &str) -> Vec<Problem> {
+    let table = match profile::load(dir, content) {
+        Ok(v) => v,
+        Err(e) => return vec![Problem::new("profile", e)],
+    };
+
+    let mut problems = Vec::new();
+    if let Err(e) = utils::which_bin(utils::BWRAP_CMD) {
+        problems.push(Problem::new(BWRAP_SECTION, e));
+    }
+
+    match table.get(BWRAP_SECTION).cloned().map(BwrapCmd::deserialize) {
+        Some(Ok(cmd)) => {
+            let found = cmd.check().into_iter();
+            problems.extend(found.map(|e| Problem::new(BWRAP_SECTION, e)));
+        }
+        Some(Err(e)) => problems.push(Problem::new(BWRAP_SECTION, e.into())),
+        None => {
+            let e = AppError::MissingSection(BWRAP_SECTION);
+            problems.push(Problem::new(BWRAP_SECTION, e));
+        }
+    }
+
+    let mut services = toml::Table::new();
+    for (key, value) in table.into_iter().filter(|(k, _)| k != BWRAP_SECTION) {
+        if key == SINGLE_INSTANCE_KEY {
+            if let Err(e) = bool::deserialize(value) {
+                problems.push(Problem::new(key, e.into()));
+            }
+            continue;
+        }
+        if key == LIFECYCLE_KEY {
+            match Lifecycle::deserialize(value) {
+                Ok(v) => problems.extend(v.check().into_iter().map(|e| Problem::new(&key, e))),
+                Err(e) => problems.push(Problem::new(key, e.into())),
+            }
+            continue;
+        }
+        if key == HOOKS_KEY {
+            match Hooks::deserialize(value) {
+                Ok(v) => problems.extend(v.check().into_iter().map(|e| Problem::new(&key, e))),
+                Err(e) => problems.push(Problem::new(key, e.into())),
+            }
+            continue;
+        }
+        if key == OUTPUT_KEY {
+            match Output::deserialize(value) {
+                Ok(v) => problems.extend(v.check().into_iter().map(|e| Problem::new(&key, e))),
+                Err(e) => problems.push(Problem::new(key, e.into())),
+            }
+            continue;
+        }
+
+        services.insert(key.clone(), value.clone());
+        let section = toml::Table::from_iter([(key.clone(), value)]);
+        let services = match toml::Value::Table(section).try_into::<ServicesConfig>() {
+            Ok(v) if v.is_empty() => {
+                let e = AppError::UnknownSection(key.clone());
+                problems.push(Problem::new(key, e));
+                continue;
+            }
+            Ok(v) => v,
+            Err(e) => {
+                problems.push(Problem::new(key, e.into()));
+                continue;
+            }
+        };
+
+        let found = services.check().into_iter();
+        problems.extend(found.map(|(name, e)| Problem::new(name, e)));
+    }
+
+    // Broken sections are reported above, order is checked only for a valid set
+    if let Ok(services) = toml::Value::Table(services).try_into::<ServicesConfig>() {
+        let found = services.check_order().into_iter();
+        problems.extend(found.map(|(name, e)| Problem::new(name, e)));
+    }
+
+    problems
+}
@@ -1,9 +1,30 @@
-use crate::{config::Cmd, services::ServicesConfig};
+use crate::{
+    app::hooks::Hooks, app::lifecycle::Lifecycle, app::output::Output, config::BwrapCmd,
+    services::ServicesConfig,
+};
 use serde::Deserialize;
 
+pub const SINGLE_INSTANCE_KEY: &str = "single_instance";
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
-    pub bwrap: Cmd,
+    pub bwrap: BwrapCmd,
+    #[serde(flatten)]
+    pub launch: LaunchConfig,
     #[serde(flatten)]
     pub services: ServicesConfig,
 }
+
+// Top level sections which control how sandbox is run, not what is inside
+#[derive(Debug, Deserialize)]
+pub struct LaunchConfig {
+    // Repeated launches are forwarded into the running sandbox
+    #[serde(default)]
+    pub single_instance: bool,
+    #[serde(default)]
+    pub lifecycle: Lifecycle,
+    #[serde(default)]
+    pub output: Output,
+    #[serde(default)]
+    pub hooks: Hooks,
+}
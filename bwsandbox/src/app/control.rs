@@ -0,0 +1,236 @@
+use crate::{
+    app::instance::Instance,
+    bwrap::{FORWARDED_SIGNALS, SandboxExit},
+    error::AppError,
+    system::PidFd,
+};
+use rustix::net::{
+    RecvAncillaryBuffer, RecvAncillaryMessage, RecvFlags, SendAncillaryBuffer,
+    SendAncillaryMessage, SendFlags,
+};
+use rustix::process::Signal;
+use serde::{Deserialize, Serialize};
+use signal_hook::iterator::Signals;
+use std::{
+    ffi::OsString,
+    io::{IoSlice, IoSliceMut, Read, Write},
+    mem::MaybeUninit,
+    net::Shutdown,
+    os::{
+        fd::{AsFd, OwnedFd},
+        unix::net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    process::{Command, ExitCode, Stdio},
+};
+
+const MAX_REQUEST: u64 = 256 * 1024;
+// stdin, stdout and stderr of the client
+const STDIO_FDS: usize = 3;
+// Ends request, signals of the client follow as single bytes until response is sent
+const REQUEST_END: u8 = b'\n';
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Request {
+    command: Vec<OsString>,
+}
+
+// Signals sent by the client before request was read
+type ReceivedRequest = (Request, [OwnedFd; STDIO_FDS], Vec<u8>);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Response {
+    exit_code: i32,
+    // Separate from exit code, `exit 130` is not SIGINT
+    signal: Option<i32>,
+}
+
+// Socket removed on drop, accept thread ends with the owner process
+#[derive(Debug)]
+pub struct ControlGuard {
+    path: PathBuf,
+}
+
+impl Drop for ControlGuard {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::error!("Failed to remove control socket: {e:?}");
+        }
+    }
+}
+
+// Only the user can connect, instances dir is 0700
+pub fn listen(id: &str, path: PathBuf) -> Result<ControlGuard, AppError> {
+    let listener = UnixListener::bind(&path).map_err(AppError::file(&path))?;
+    let guard = ControlGuard { path };
+
+    let id = id.to_owned();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Control socket accept failed: {e:?}");
+                    continue;
+                }
+            };
+            // Every command lives as long as it wants, do not block next launches
+            let id = id.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = serve(&stream, &id) {
+                    log::error!("Control request failed: {e}");
+                }
+            });
+        }
+    });
+    Ok(guard)
+}
+
+// Commands run by `enter` as separate process, setns is not allowed for multithreaded owner
+fn serve(stream: &UnixStream, id: &str) -> Result<(), AppError> {
+    let (request, [stdin, stdout, stderr], early) = recv_request(stream)?;
+    log::info!("Forwarded launch: {:?}", request.command);
+
+    let exe = std::env::current_exe().map_err(AppError::io("current exe"))?;
+    let mut child = Command::new(&exe)
+        .args(["enter", id, "--"])
+        .args(&request.command)
+        .stdin(Stdio::from(stdin))
+        .stdout(Stdio::from(stdout))
+        .stderr(Stdio::from(stderr))
+        .spawn()
+        .map_err(AppError::spawn(exe.to_string_lossy()))?;
+    // Opened before wait, signals after exit can't hit a reused pid
+    let pidfd = PidFd::from_pid(child.id())?;
+    let reader = stream.try_clone().map_err(AppError::io("control socket"))?;
+    std::thread::spawn(move || relay_signals(&reader, &early, &pidfd));
+    let status = child.wait().map_err(AppError::io("forwarded command"))?;
+
+    let exit = SandboxExit::from_status(status);
+    let response = Response {
+        exit_code: exit.exit_code,
+        signal: exit.signal,
+    };
+    let response = serde_json::to_vec(&response).map_err(AppError::json("control response"))?;
+    let mut stream = stream;
+    stream
+        .write_all(&response)
+        .and_then(|()| stream.shutdown(Shutdown::Both))
+        .map_err(AppError::io("control socket"))
+}
+
+// `enter` passes them to the command, closed client is a hangup like for a terminal
+fn relay_signals(mut stream: &UnixStream, early: &[u8], pidfd: &PidFd) {
+    let send = |sig: u8| {
+        if let Some(signal) = Signal::from_named_raw(i32::from(sig)) {
+            let _ = pidfd.send_sig(signal);
+        }
+    };
+    early.iter().copied().for_each(send);
+    let mut buf = [0; 1];
+    while let Ok(1) = stream.read(&mut buf) {
+        send(buf[0]);
+    }
+    let _ = pidfd.send_sig(Signal::HUP);
+}
+
+fn recv_request(stream: &UnixStream) -> Result<ReceivedRequest, AppError> {
+    let mut buf = vec![0; 4096];
+    let mut space = [MaybeUninit::uninit(); rustix::cmsg_space!(ScmRights(STDIO_FDS))];
+    let mut control = RecvAncillaryBuffer::new(&mut space);
+    let msg = rustix::net::recvmsg(
+        stream,
+        &mut [IoSliceMut::new(&mut buf)],
+        &mut control,
+        RecvFlags::CMSG_CLOEXEC,
+    )
+    .map_err(|e| AppError::io("control socket")(e.into()))?;
+
+    let mut fds = Vec::new();
+    for it in control.drain() {
+        if let RecvAncillaryMessage::ScmRights(v) = it {
+            fds.extend(v);
+        }
+    }
+
+    // File descriptors come with the first chunk, the rest is plain data until request end
+    buf.truncate(msg.bytes);
+    let invalid = || AppError::io("control socket")(std::io::ErrorKind::InvalidData.into());
+    let mut rest = stream.take(MAX_REQUEST);
+    let mut byte = [0; 1];
+    while !buf.contains(&REQUEST_END) {
+        match rest.read(&mut byte) {
+            Ok(1) => buf.push(byte[0]),
+            Ok(_) => return Err(invalid()),
+            Err(e) => return Err(AppError::io("control socket")(e)),
+        }
+    }
+    let end = buf
+        .iter()
+        .position(|v| *v == REQUEST_END)
+        .unwrap_or_default();
+    let early = buf.split_off(end + 1);
+    let request = serde_json::from_slice(&buf).map_err(AppError::json("control request"))?;
+    let fds = fds.try_into().map_err(|_| invalid())?;
+    Ok((request, fds, early))
+}
+
+// Client side, stdio is passed along so forwarded command behaves as launched from here
+pub fn forward(instance: &Instance, command: Vec<OsString>) -> Result<ExitCode, AppError> {
+    let path = instance
+        .control
+        .as_ref()
+        .ok_or_else(|| AppError::InstanceNotFound(instance.id.clone()))?;
+    // Registered before connect, so the command gets every signal sent to this launch
+    let mut signals = Signals::new(FORWARDED_SIGNALS).map_err(AppError::CtrlC)?;
+    let handle = signals.handle();
+    let stream = UnixStream::connect(path).map_err(AppError::file(path))?;
+    log::info!("Forward launch to running sandbox {}", instance.id);
+
+    let mut request =
+        serde_json::to_vec(&Request { command }).map_err(AppError::json("control request"))?;
+    request.push(REQUEST_END);
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    let stderr = std::io::stderr();
+    let fds = [stdin.as_fd(), stdout.as_fd(), stderr.as_fd()];
+    let mut space = [MaybeUninit::uninit(); rustix::cmsg_space!(ScmRights(STDIO_FDS))];
+    let mut control = SendAncillaryBuffer::new(&mut space);
+    control.push(SendAncillaryMessage::ScmRights(&fds));
+    let sent = rustix::net::sendmsg(
+        &stream,
+        &[IoSlice::new(&request)],
+        &mut control,
+        SendFlags::empty(),
+    )
+    .map_err(|e| AppError::io("control socket")(e.into()))?;
+
+    let mut writer = &stream;
+    writer
+        .write_all(&request[sent..])
+        .map_err(AppError::io("control socket"))?;
+
+    let writer = stream.try_clone().map_err(AppError::io("control socket"))?;
+    let relay = std::thread::spawn(move || {
+        for sig in signals.forever() {
+            let Ok(sig) = u8::try_from(sig) else {
+                continue;
+            };
+            if (&writer).write_all(&[sig]).is_err() {
+                break;
+            }
+        }
+    });
+    let response = serde_json::from_reader(&stream);
+    handle.close();
+    let _ = relay.join();
+    let response: Response = response.map_err(AppError::json("control response"))?;
+    let exit = SandboxExit {
+        exit_code: response.exit_code,
+        signal: response.signal,
+    };
+    if let Some(signal) = exit.signal {
+        log::info!("Forwarded command killed by signal {signal}");
+    }
+    Ok(exit.exit_code())
+}
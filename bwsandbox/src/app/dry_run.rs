@@ -0,0 +1,76 @@
+use crate::{error::AppError, utils};
+use serde::Serialize;
+use std::{ffi::OsStr, process::Command};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Shell,
+    Json,
+}
+
+impl Format {
+    pub fn from_arg(arg: Option<&OsStr>) -> Result<Self, AppError> {
+        match arg.map(|v| v.to_str()) {
+            None | Some(Some("shell")) => Ok(Self::Shell),
+            Some(Some("json")) => Ok(Self::Json),
+            Some(_) => Err(AppError::BadArgs),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CommandDump {
+    name: String,
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandDump {
+    fn new(name: &str, command: &Command) -> Self {
+        let lossy = |v: &OsStr| v.to_string_lossy().into_owned();
+        Self {
+            name: name.into(),
+            program: lossy(command.get_program()),
+            args: command.get_args().map(lossy).collect(),
+        }
+    }
+
+    fn to_shell(&self) -> Result<String, AppError> {
+        let words =
+            std::iter::once(self.program.as_str()).chain(self.args.iter().map(String::as_str));
+        let line = shlex::try_join(words)?;
+        Ok(format!("# {}\n{line}", self.name))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Dump {
+    bwrap: CommandDump,
+    services: Vec<CommandDump>,
+}
+
+// Services commands are printed as configured, runtime args (f.e. sandbox pid) are added on start
+pub fn print<'a, I>(format: Format, bwrap: &Command, services: I) -> Result<(), AppError>
+where
+    I: Iterator<Item = (&'a str, &'a Command)>,
+{
+    let dump = Dump {
+        bwrap: CommandDump::new(utils::BWRAP_CMD, bwrap),
+        services: services.map(|(n, c)| CommandDump::new(n, c)).collect(),
+    };
+
+    match format {
+        Format::Shell => {
+            println!("{}", dump.bwrap.to_shell()?);
+            for it in &dump.services {
+                println!("{}", it.to_shell()?);
+            }
+        }
+        Format::Json => {
+            let json = serde_json::to_string_pretty(&dump).map_err(AppError::json("dry-run"))?;
+            println!("{json}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,213 @@
+use crate::{
+    app::instance::Instance,
+    bwrap::{FORWARDED_SIGNALS, SandboxExit, app_pid},
+    error::AppError,
+    system::{Capabilities, Namespace, NamespaceType, PidFd, SeccompFilter, cgroup},
+};
+use rustix::fs::{Mode, OFlags};
+use rustix::process::Signal;
+use std::{
+    ffi::OsString,
+    os::{
+        fd::OwnedFd,
+        unix::{ffi::OsStrExt, process::CommandExt},
+    },
+    path::PathBuf,
+    process::{Command, ExitCode, ExitStatus},
+};
+
+// User namespaces are joined separately, each one before the namespaces it owns
+const NAMESPACES: [NamespaceType; 5] = [
+    NamespaceType::Mnt,
+    NamespaceType::Pid,
+    NamespaceType::Net,
+    NamespaceType::Ipc,
+    NamespaceType::Uts,
+];
+
+// Everything is opened before the first setns, host /proc is gone after mount namespace switch
+struct Target {
+    // Outermost first, every sandbox user namespace down to the app one
+    userns: Vec<Namespace>,
+    // With inode of the owner user namespace
+    namespaces: Vec<(Namespace, u64)>,
+    env: Vec<(OsString, OsString)>,
+    cwd: OwnedFd,
+    // setns into user namespace grants full capabilities, the app has only these
+    caps: Capabilities,
+    seccomp: Option<SeccompFilter>,
+}
+
+pub fn run(instance: &Instance, command: &[OsString]) -> Result<ExitCode, AppError> {
+    let (program, args) = command.split_first().ok_or(AppError::BadArgs)?;
+    let target = Target::open(instance)?;
+    let own_userns = own_inode(NamespaceType::User)?;
+    // Entered command is counted, frozen and killed with the sandbox, host cgroupfs is gone after setns
+    if let Some(path) = &instance.cgroup {
+        cgroup::add_pid(path, std::process::id())?;
+    }
+
+    // setns into user namespace is allowed only for single threaded process, so no threads here
+    let mut pending = target.namespaces;
+    let steps = std::iter::once(None).chain(target.userns.iter().map(Some));
+    for userns in steps {
+        let owner = match userns {
+            Some(v) => {
+                v.enter()?;
+                v.fd_inode()?
+            }
+            None => own_userns,
+        };
+        let (now, rest): (Vec<_>, Vec<_>) = pending.into_iter().partition(|v| v.1 == owner);
+        for (ns, _) in now {
+            ns.enter()?;
+        }
+        pending = rest;
+    }
+    for (ns, _) in pending {
+        ns.enter()?;
+    }
+    rustix::process::fchdir(&target.cwd)
+        .map_err(|e| AppError::file("/proc/<app>/cwd")(e.into()))?;
+
+    // Pid namespace applies only to children, so spawn instead of exec
+    let mut cmd = Command::new(program);
+    cmd.args(args).env_clear().envs(target.env);
+    let (caps, seccomp) = (target.caps, target.seccomp);
+    let restrict = move || {
+        caps.apply()?;
+        match &seccomp {
+            Some(filter) => filter.load(),
+            None => Ok(()),
+        }
+    };
+    unsafe { cmd.pre_exec(restrict) };
+
+    let name = program.to_string_lossy();
+    log::info!("Entering sandbox {} with {name:?}", instance.id);
+    let status = spawn_relayed(&mut cmd).map_err(AppError::spawn(name.as_ref()))?;
+    let exit = SandboxExit::from_status(status);
+    // Die the same way, so forwarding owner sees the signal and not 128 + signal exit code
+    if let Some(signal) = exit.signal {
+        let _ = signal_hook::low_level::emulate_default_handler(signal);
+    }
+    Ok(exit.exit_code())
+}
+
+// Signals sent to enter, f.e. by forwarding owner, reach the command. Namespaces are joined
+// already, so relay thread is allowed
+fn spawn_relayed(cmd: &mut Command) -> Result<ExitStatus, std::io::Error> {
+    use signal_hook::iterator::Signals;
+
+    // Registered before spawn, so no signal is lost while command starts
+    let mut signals = Signals::new(FORWARDED_SIGNALS)?;
+    let handle = signals.handle();
+    let mut child = cmd.spawn()?;
+    let pidfd = PidFd::from_pid(child.id()).map_err(std::io::Error::other)?;
+    let relay = std::thread::spawn(move || {
+        for sig in signals.forever() {
+            if let Some(signal) = Signal::from_named_raw(sig) {
+                let _ = pidfd.send_sig(signal);
+            }
+        }
+    });
+
+    let status = child.wait();
+    handle.close();
+    let _ = relay.join();
+    status
+}
+
+impl Target {
+    fn open(instance: &Instance) -> Result<Self, AppError> {
+        let pid = app_pid(instance.sandbox.child_pid);
+
+        let mut namespaces = Vec::new();
+        for ty in NAMESPACES {
+            let ns = Namespace::open_pid(pid, ty)?;
+            // Not unshared by profile
+            if ns.fd_inode()? == own_inode(ty)? {
+                continue;
+            }
+            let owner = ns.get_userns()?.fd_inode()?;
+            namespaces.push((ns, owner));
+        }
+
+        let path = PathBuf::from(format!("/proc/{pid}/cwd"));
+        let flags = OFlags::PATH | OFlags::DIRECTORY | OFlags::CLOEXEC;
+        let cwd = rustix::fs::open(&path, flags, Mode::empty())
+            .map_err(|e| AppError::file(&path)(e.into()))?;
+
+        Ok(Self {
+            userns: userns_chain(pid)?,
+            namespaces,
+            env: environ(pid)?,
+            cwd,
+            caps: Capabilities::of_pid(pid)?,
+            seccomp: seccomp(instance)?,
+        })
+    }
+}
+
+fn own_inode(ty: NamespaceType) -> Result<u64, AppError> {
+    let ns = Namespace::open_pid(std::process::id(), ty)?;
+    Ok(ns.fd_inode()?)
+}
+
+// bwrap may add nested user namespace (f.e. for --disable-userns), other namespaces stay owned by outer one
+fn userns_chain(pid: u32) -> Result<Vec<Namespace>, AppError> {
+    let own = own_inode(NamespaceType::User)?;
+    let mut chain = Vec::new();
+    let mut current = Namespace::open_pid(pid, NamespaceType::User)?;
+    while current.fd_inode()? != own {
+        let parent = current.parent()?;
+        chain.push(current);
+        current = parent;
+    }
+    chain.reverse();
+    Ok(chain)
+}
+
+fn environ(pid: u32) -> Result<Vec<(OsString, OsString)>, AppError> {
+    let path = PathBuf::from(format!("/proc/{pid}/environ"));
+    let content = std::fs::read(&path).map_err(AppError::file(&path))?;
+    Ok(parse_environ(&content))
+}
+
+fn parse_environ(content: &[u8]) -> Vec<(OsString, OsString)> {
+    content
+        .split(|v| *v == 0)
+        .filter_map(|v| {
+            let pos = v.iter().position(|v| *v == b'=')?;
+            let key = std::ffi::OsStr::from_bytes(&v[..pos]);
+            let value = std::ffi::OsStr::from_bytes(&v[pos + 1..]);
+            Some((key.into(), value.into()))
+        })
+        .collect()
+}
+
+// Owner keeps the compiled filter open for the sandbox lifetime
+fn seccomp(instance: &Instance) -> Result<Option<SeccompFilter>, AppError> {
+    let Some(fd) = instance.seccomp_fd else {
+        return Ok(None);
+    };
+    let path = PathBuf::from(format!("/proc/{}/fd/{fd}", instance.owner_pid));
+    let bpf = std::fs::read(&path).map_err(AppError::file(&path))?;
+    Ok(Some(SeccompFilter::from_bytes(&bpf)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_environ() {
+        let env = parse_environ(b"HOME=/home/user\0EMPTY=\0A=b=c\0BROKEN\0");
+        let expected = [("HOME", "/home/user"), ("EMPTY", ""), ("A", "b=c")];
+        assert!(
+            env.iter()
+                .map(|(k, v)| (k.to_str().unwrap(), v.to_str().unwrap()))
+                .eq(expected)
+        );
+    }
+}
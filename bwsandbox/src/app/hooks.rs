@@ -0,0 +1,229 @@
+use crate::{bwrap::SandboxExit, error::AppError, services::BwrapInfo, system::PidFd, utils};
+use rustix::process::Signal;
+use serde::Deserialize;
+use std::{
+    ffi::OsString,
+    path::Path,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+pub const HOOKS_KEY: &str = "hooks";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const ENV_PREFIX: &str = "BWSANDBOX_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    // Before bwrap is launched, nothing of the sandbox exists yet
+    PreSpawn,
+    // Services are ready, sandboxed app is still blocked
+    PostStart,
+    // Sandbox and services are stopped
+    PostExit,
+}
+
+impl Phase {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::PreSpawn => "pre_spawn",
+            Self::PostStart => "post_start",
+            Self::PostExit => "post_exit",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hook {
+    phase: Phase,
+    // Host command with args, no shell involved
+    command: Vec<String>,
+    // Seconds, hook is killed after that
+    timeout: Option<f64>,
+    // Failed pre_spawn and post_start hooks abort launch unless set
+    #[serde(default)]
+    ignore_failure: bool,
+}
+
+impl Hook {
+    fn timeout(&self) -> Result<Duration, AppError> {
+        match self.timeout {
+            Some(value) => Duration::try_from_secs_f64(value)
+                .map_err(|_| AppError::Hook(format!("invalid timeout {value}"))),
+            None => Ok(DEFAULT_TIMEOUT),
+        }
+    }
+
+    fn run(&self, env: &HookEnv) -> Result<(), AppError> {
+        let Some((bin, args)) = self.command.split_first() else {
+            return Err(AppError::Hook("empty command".into()));
+        };
+        let timeout = self.timeout()?;
+        log::info!("Running {} hook: {:?}", self.phase.as_str(), self.command);
+
+        let mut child = Command::new(bin)
+            .args(args)
+            .envs(
+                env.vars
+                    .iter()
+                    .map(|(k, v)| (format!("{ENV_PREFIX}{k}"), v)),
+            )
+            .stdin(Stdio::null())
+            .spawn()
+            .map_err(AppError::spawn(bin))?;
+        let pidfd = PidFd::from_pid(child.id())?;
+        if pidfd.wait(timeout).is_err() {
+            let _ = pidfd.send_sig(Signal::KILL);
+            let _ = child.wait();
+            return Err(AppError::Hook(format!("{bin} timed out")));
+        }
+
+        let status = child.wait().map_err(AppError::spawn(bin))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(AppError::Hook(format!("{bin} failed with {status}")))
+        }
+    }
+}
+
+// Ordered as in profile, inherited hooks go first
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Hooks(Vec<Hook>);
+
+impl Hooks {
+    pub fn run(&self, phase: Phase, env: &HookEnv) -> Result<(), AppError> {
+        let env = env.clone().with("PHASE", phase.as_str());
+        for it in self.0.iter().filter(|v| v.phase == phase) {
+            match it.run(&env) {
+                Ok(()) => {}
+                // Nothing left to abort after exit
+                Err(e) if it.ignore_failure || phase == Phase::PostExit => {
+                    log::error!("{} hook failed: {e}", phase.as_str());
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check(&self) -> Vec<AppError> {
+        let mut problems = Vec::new();
+        for it in &self.0 {
+            match it.command.first() {
+                Some(bin) => problems.extend(utils::which_bin(bin).err()),
+                None => problems.push(AppError::Hook("empty command".into())),
+            }
+            problems.extend(it.timeout().err());
+        }
+        problems
+    }
+}
+
+// Passed to hooks as BWSANDBOX_* variables
+#[derive(Debug, Clone)]
+pub struct HookEnv {
+    vars: Vec<(&'static str, OsString)>,
+}
+
+impl HookEnv {
+    pub fn new(id: &str, profile: &Path) -> Self {
+        let env = Self { vars: Vec::new() };
+        env.with("ID", id)
+            .with("PROFILE", profile)
+            .with("WORKDIR", utils::temp_dir())
+    }
+
+    fn with(mut self, key: &'static str, value: impl Into<OsString>) -> Self {
+        self.vars.push((key, value.into()));
+        self
+    }
+
+    // Namespaces of the sandboxed app, f.e. BWSANDBOX_NS_NET=/proc/<pid>/ns/net
+    pub fn with_sandbox(self, info: &BwrapInfo, cgroup: Option<&Path>) -> Self {
+        let sandbox = info.sandbox;
+        let pid = sandbox.child_pid;
+        // Only namespaces unshared by bwrap are reported, user namespace always exists
+        let namespaces = [
+            ("NS_USER", "user", true),
+            ("NS_MNT", "mnt", sandbox.mnt_namespace.is_some()),
+            ("NS_PID", "pid", sandbox.pid_namespace.is_some()),
+            ("NS_NET", "net", sandbox.net_namespace.is_some()),
+            ("NS_IPC", "ipc", sandbox.ipc_namespace.is_some()),
+            ("NS_UTS", "uts", sandbox.uts_namespace.is_some()),
+            ("NS_CGROUP", "cgroup", sandbox.cgroup_namespace.is_some()),
+        ];
+        let mut env = self
+            .with("BWRAP_PID", info.pid.to_string())
+            .with("CHILD_PID", pid.to_string());
+        for (key, name, _) in namespaces.iter().filter(|v| v.2) {
+            env = env.with(key, format!("/proc/{pid}/ns/{name}"));
+        }
+        match cgroup {
+            Some(path) => env.with("CGROUP", path),
+            None => env,
+        }
+    }
+
+    pub fn with_exit(self, exit: &SandboxExit) -> Self {
+        let env = self.with("EXIT_CODE", exit.exit_code.to_string());
+        match exit.signal {
+            Some(signal) => env.with("SIGNAL", signal.to_string()),
+            None => env,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(config: &str) -> Hooks {
+        let table: toml::Table = toml::from_str(config).unwrap();
+        Hooks::deserialize(table[HOOKS_KEY].clone()).unwrap()
+    }
+
+    #[test]
+    fn test_hooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let script = format!(
+            "echo $BWSANDBOX_PHASE $BWSANDBOX_EXIT_CODE >> {}",
+            out.display()
+        );
+        let hooks = parse(&format!(
+            r#"
+            [[hooks]]
+            phase = "post_exit"
+            command = ["sh", "-c", "{script}"]
+
+            [[hooks]]
+            phase = "pre_spawn"
+            command = ["false"]
+            ignore_failure = true
+
+            [[hooks]]
+            phase = "pre_spawn"
+            command = ["sh", "-c", "{script}"]
+            "#
+        ));
+        assert!(hooks.check().is_empty());
+
+        let env = HookEnv::new("test", Path::new("test.toml"));
+        hooks.run(Phase::PreSpawn, &env).unwrap();
+        hooks.run(Phase::PostStart, &env).unwrap();
+        let env = env.with_exit(&SandboxExit::from_code(3));
+        hooks.run(Phase::PostExit, &env).unwrap();
+        let out = std::fs::read_to_string(out).unwrap();
+        assert_eq!(out, "pre_spawn\npost_exit 3\n");
+
+        let hooks =
+            parse(r#"hooks = [{ phase = "post_start", command = ["sleep", "5"], timeout = 0.1 }]"#);
+        assert!(hooks.run(Phase::PostStart, &env).is_err());
+
+        let hooks = parse(r#"hooks = [{ phase = "post_exit", command = [], timeout = -1 }]"#);
+        assert_eq!(hooks.check().len(), 2);
+    }
+}
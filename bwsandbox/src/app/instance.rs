@@ -0,0 +1,196 @@
+use crate::{
+    bwrap::{self, SandboxStatus, StopPolicy},
+    error::AppError,
+    system::PidFd,
+    utils,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const RECORD_EXT: &str = "json";
+const CONTROL_EXT: &str = "sock";
+const LOCK_EXT: &str = "lock";
+
+// Record of a running sandbox, kept outside of workdir because workdir is writable from sandbox
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Instance {
+    pub id: String,
+    pub profile: PathBuf,
+    pub command: Vec<String>,
+    pub bwrap_pid: u32,
+    pub sandbox: SandboxStatus,
+    pub services: Vec<String>,
+    pub cgroup: Option<PathBuf>,
+    // bwsandbox process which owns the sandbox
+    pub owner_pid: u32,
+    // Compiled seccomp filter, open in owner process
+    pub seccomp_fd: Option<i32>,
+    // Control socket of single instance profile
+    pub control: Option<PathBuf>,
+    pub stop: StopPolicy,
+    // Unix time in seconds
+    pub started: u64,
+}
+
+// Record removed on drop
+#[derive(Debug)]
+pub struct InstanceGuard {
+    path: PathBuf,
+}
+
+impl Drop for InstanceGuard {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::error!("Failed to remove instance record: {e:?}");
+        }
+    }
+}
+
+fn dir() -> PathBuf {
+    utils::runtime_dir().join(format!("{}-instances", utils::APP_NAME))
+}
+
+// Private to the user, control sockets rely on it
+fn create_dir() -> Result<PathBuf, AppError> {
+    let dir = dir();
+    std::fs::create_dir_all(&dir).map_err(AppError::file(&dir))?;
+    std::fs::set_permissions(&dir, PermissionsExt::from_mode(0o700))
+        .map_err(AppError::file(&dir))?;
+    Ok(dir)
+}
+
+pub fn control_path(id: &str) -> PathBuf {
+    dir().join(id).with_extension(CONTROL_EXT)
+}
+
+// Held from lookup of running instance until new one is registered, so parallel launches start
+// only one sandbox. Lock file is never removed, removal would race with the next lock.
+pub fn lock_profile(name: &str) -> Result<std::fs::File, AppError> {
+    let path = create_dir()?.join(name).with_extension(LOCK_EXT);
+    let file = std::fs::File::create(&path).map_err(AppError::file(&path))?;
+    file.lock().map_err(AppError::file(&path))?;
+    Ok(file)
+}
+
+fn record_path(id: &str) -> Result<PathBuf, AppError> {
+    if !utils::is_rand_id(id) {
+        return Err(AppError::InstanceNotFound(id.into()));
+    }
+    Ok(dir().join(id).with_extension(RECORD_EXT))
+}
+
+pub fn now() -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH);
+    now.map(|v| v.as_secs()).unwrap_or_default()
+}
+
+impl Instance {
+    pub fn register(&self) -> Result<InstanceGuard, AppError> {
+        create_dir()?;
+        let path = record_path(&self.id)?;
+        let json = serde_json::to_vec_pretty(self).map_err(AppError::json("instance record"))?;
+        // Readers never see partially written record
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json).map_err(AppError::file(&tmp))?;
+        std::fs::rename(&tmp, &path).map_err(AppError::file(&path))?;
+        Ok(InstanceGuard { path })
+    }
+
+    pub fn load(id: &str) -> Result<Self, AppError> {
+        let path = record_path(id)?;
+        let Some(instance) = Self::from_file(&path)? else {
+            return Err(AppError::InstanceNotFound(id.into()));
+        };
+        Ok(instance)
+    }
+
+    // Records left after crash are removed on the way
+    pub fn list() -> Result<Vec<Self>, AppError> {
+        let dir = dir();
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(AppError::file(&dir)(e)),
+        };
+
+        let mut found = Vec::new();
+        for it in entries {
+            let path = it.map_err(AppError::file(&dir))?.path();
+            if path.extension().is_none_or(|v| v != RECORD_EXT) {
+                continue;
+            }
+            match Self::from_file(&path) {
+                Ok(Some(instance)) => found.push(instance),
+                Ok(None) => {}
+                Err(e) => log::warn!("Skip broken instance record: {e}"),
+            }
+        }
+        found.sort_by_key(|v| v.started);
+        Ok(found)
+    }
+
+    pub fn find_single(profile: &Path) -> Result<Option<Self>, AppError> {
+        let found = Self::list()?.into_iter();
+        Ok(found
+            .rev()
+            .find(|v| v.profile == profile && v.control.is_some()))
+    }
+
+    fn from_file(path: &Path) -> Result<Option<Self>, AppError> {
+        let content = match std::fs::read(path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(AppError::file(path)(e)),
+        };
+        let instance: Self =
+            serde_json::from_slice(&content).map_err(AppError::json("instance record"))?;
+
+        if instance.pidfd().is_none() {
+            log::info!("Remove stale instance record: {}", path.display());
+            std::fs::remove_file(path).map_err(AppError::file(path))?;
+            return Ok(None);
+        }
+        Ok(Some(instance))
+    }
+
+    // Pid may be reused after crash, so pid namespace must match as well
+    fn pidfd(&self) -> Option<PidFd> {
+        let pid = self.sandbox.child_pid;
+        let pidfd = PidFd::from_pid(pid).ok()?;
+        if let Some(ns) = self.sandbox.pid_namespace {
+            let meta = std::fs::metadata(format!("/proc/{pid}/ns/pid")).ok()?;
+            if meta.ino() != u64::from(ns) {
+                return None;
+            }
+        }
+        Some(pidfd)
+    }
+
+    // Owner process removes record and cleans up everything after bwrap exit
+    pub fn stop(&self) -> Result<(), AppError> {
+        let child = self
+            .pidfd()
+            .ok_or_else(|| AppError::InstanceNotFound(self.id.clone()))?;
+        let bwrap = PidFd::from_pid(self.bwrap_pid).ok();
+
+        bwrap::terminate(
+            &child,
+            self.sandbox.child_pid,
+            self.cgroup.as_deref(),
+            self.stop,
+        );
+        if let Some(bwrap) = bwrap {
+            let _ = bwrap.wait(self.stop.timeout);
+        }
+        Ok(())
+    }
+
+    pub fn profile_name(&self) -> std::borrow::Cow<'_, str> {
+        let name = self.profile.file_stem().unwrap_or(self.profile.as_os_str());
+        name.to_string_lossy()
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{bwrap::StopPolicy, error::AppError, services::READY_SERVICES};
+use rustix::process::Signal;
+use serde::Deserialize;
+use std::{collections::BTreeMap, time::Duration};
+
+pub const LIFECYCLE_KEY: &str = "lifecycle";
+
+// Timeouts are in seconds, fractions allowed
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Lifecycle {
+    // Wait after SIGTERM before escalation
+    stop_timeout: Option<f64>,
+    // Sent when app ignores SIGTERM, SIGKILL follows after another stop_timeout
+    kill_signal: Option<String>,
+    // By service section, f.e. `dbus = 10`
+    #[serde(default)]
+    ready_timeout: BTreeMap<String, f64>,
+}
+
+impl Lifecycle {
+    pub fn stop_policy(&self) -> Result<StopPolicy, AppError> {
+        let mut policy = StopPolicy::default();
+        if let Some(value) = self.stop_timeout {
+            policy.timeout = duration("stop_timeout", value)?;
+        }
+        if let Some(name) = &self.kill_signal {
+            policy.signal = parse_signal(name)?;
+        }
+        Ok(policy)
+    }
+
+    // Service name with its timeout
+    pub fn ready_timeouts(&self) -> Result<Vec<(&'static str, Duration)>, AppError> {
+        let mut timeouts = Vec::new();
+        for (section, value) in &self.ready_timeout {
+            let Some((_, name)) = READY_SERVICES.iter().find(|v| v.0 == section) else {
+                let reason = format!("service [{section}] has no ready timeout");
+                return Err(AppError::Lifecycle(reason));
+            };
+            timeouts.push((*name, duration(section, *value)?));
+        }
+        Ok(timeouts)
+    }
+
+    pub fn check(&self) -> Vec<AppError> {
+        let stop = self.stop_policy().err();
+        let ready = self.ready_timeouts().err();
+        stop.into_iter().chain(ready).collect()
+    }
+}
+
+fn duration(name: &str, value: f64) -> Result<Duration, AppError> {
+    Duration::try_from_secs_f64(value)
+        .map_err(|_| AppError::Lifecycle(format!("invalid {name} timeout {value}")))
+}
+
+// Names as in signal(7), with or without SIG prefix
+fn parse_signal(name: &str) -> Result<i32, AppError> {
+    let full = if name.starts_with("SIG") {
+        name.to_owned()
+    } else {
+        format!("SIG{name}")
+    };
+    (1..32)
+        .filter(|v| Signal::from_named_raw(*v).is_some())
+        .find(|v| signal_hook::low_level::signal_name(*v) == Some(full.as_str()))
+        .ok_or_else(|| AppError::Lifecycle(format!("unknown kill_signal {name:?}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lifecycle() {
+        let config: Lifecycle = toml::from_str(
+            r#"
+            stop_timeout = 2.5
+            kill_signal = "INT"
+            ready_timeout = { dbus = 10, pasta = 0.5 }
+            "#,
+        )
+        .unwrap();
+        let policy = config.stop_policy().unwrap();
+        assert_eq!(policy.timeout, Duration::from_millis(2500));
+        assert_eq!(policy.signal, Signal::INT.as_raw());
+        let timeouts = config.ready_timeouts().unwrap();
+        assert_eq!(timeouts.len(), 2);
+        assert!(timeouts.contains(&(
+            crate::services::READY_SERVICES[0].1,
+            Duration::from_secs(10)
+        )));
+
+        let policy = Lifecycle::default().stop_policy().unwrap();
+        assert_eq!(policy.signal, Signal::KILL.as_raw());
+
+        let cases = [
+            "stop_timeout = -1",
+            r#"kill_signal = "SIGNOPE""#,
+            "ready_timeout = { seccomp = 1 }",
+        ];
+        for value in cases {
+            let config: Lifecycle = toml::from_str(value).unwrap();
+            assert!(!config.check().is_empty(), "{value}");
+        }
+    }
+}
@@ -1,35 +1,300 @@
-use crate::{bwrap::ProcBuilder, error::AppError, utils};
-pub use args::Args;
-use std::process::ExitStatus;
+use crate::{
+    bwrap::{ExitReport, ProcBuilder, ServiceUsage},
+    error::AppError,
+    services::{self, BwrapInfo, Context, HandleType, Service},
+    system::LogCapture,
+};
+pub use args::{Args, CheckArgs, Cli};
+use std::{ffi::OsString, process::ExitCode, time::Duration};
 
 mod args;
+mod auto;
+mod check;
 mod config;
+mod control;
 mod current_dir;
+mod dry_run;
+mod enter;
+mod hooks;
+mod instance;
+mod lifecycle;
+mod output;
+mod overrides;
+mod policy;
+mod profile;
+mod search_path;
 
 pub struct App;
 impl App {
-    pub fn start(args: Args) -> Result<ExitStatus, AppError> {
-        let (mut services, bwrap_args) = current_dir::run_in_dir(&args.config_dir, move || {
-            let config: config::Config = utils::deserialize(&args.config)?;
-            let bwrap_args = config.bwrap.collect_args()?;
-            let services = config.services.load()?;
-            Ok((services, bwrap_args))
+    pub fn start(args: Args) -> Result<ExitCode, AppError> {
+        let policy = policy::Policy::load()?;
+        let config::Config {
+            bwrap,
+            launch,
+            services,
+        } = load_config(&args, policy.as_ref())?;
+
+        let profile_name = args.config_file.file_stem().unwrap_or_default();
+        let profile_name = profile_name.to_string_lossy().into_owned();
+        // Held until the new instance is registered, parallel launches must not both start
+        let lock = if launch.single_instance && args.dry_run.is_none() {
+            let lock = instance::lock_profile(&profile_name)?;
+            if let Some(running) = instance::Instance::find_single(&args.config_file)? {
+                // Forwarded command may run long, later launches must not wait for it
+                drop(lock);
+                let command = std::iter::once(args.app).chain(args.app_args).collect();
+                return control::forward(&running, command);
+            }
+            Some(lock)
+        } else {
+            None
+        };
+
+        // Services connect to helpers and compile filters, forwarded launch needs none of it
+        let (mut services, bwrap_args) = current_dir::run_in_dir(&args.config_dir, || {
+            let bwrap_args = bwrap.collect_args()?;
+            Ok((services.load()?, bwrap_args))
         })?;
 
-        let mut bwrap_builder = ProcBuilder::new(args.app, bwrap_args);
+        let command = std::iter::once(&args.app)
+            .chain(&args.app_args)
+            .map(|v| v.to_string_lossy().into_owned())
+            .collect();
+        let mut bwrap_builder = ProcBuilder::new(args.app, bwrap_args)?;
+        bwrap_builder.set_pty(args.pty);
         let _cleanup = bwrap_builder.apply_services(&mut services)?;
 
-        let proc = bwrap_builder.spawn(args.app_args)?;
+        let net_service = services.iter().any(|v| services::is_net_service(v.name()));
+        bwrap
+            .lint
+            .run(bwrap_builder.args(), net_service, args.pty)?;
+        if let Some(policy) = &policy {
+            policy.check(bwrap_builder.args())?;
+        }
+
+        if let Some(format) = args.dry_run {
+            let command = bwrap_builder.into_command(args.app_args);
+            let services = services
+                .iter()
+                .filter_map(|v| Some((v.name(), v.command()?)));
+            dry_run::print(format, &command, services)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        // Files and helper connections behind the args, dry run stops before them
+        current_dir::run_in_dir(&args.config_dir, || {
+            bwrap.write_files()?;
+            services.iter_mut().try_for_each(Service::prepare)
+        })?;
+
+        let seccomp_fd = bwrap_builder
+            .args()
+            .iter()
+            .find(|v| v.is("--seccomp"))
+            .and_then(|v| v.values().first()?.to_str()?.parse().ok());
+        let app_output = launch.output.capture_app(&profile_name, args.pty)?;
+        bwrap_builder.set_output(app_output.pipes);
+        let _services_output = capture_services(&launch.output, &profile_name, &mut services)?;
+
+        let stop = launch.lifecycle.stop_policy()?;
+        let ready_timeouts = launch.lifecycle.ready_timeouts()?;
+        let hook_env = hooks::HookEnv::new(crate::utils::sandbox_id(), &args.config_file);
+        launch.hooks.run(hooks::Phase::PreSpawn, &hook_env)?;
+        let proc = bwrap_builder.spawn(args.app_args)?.with_stop_policy(stop);
         let proc_status = proc.bwrap_info();
-        let _handles = services
-            .into_iter()
-            .map(|v| {
-                log::info!("Starting '{}' service", v.name());
-                v.start(&proc_status)
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+        let handles = start_services(services, proc_status, &ready_timeouts)?;
+        let hook_env = hook_env.with_sandbox(&proc_status, proc.cgroup());
+        launch.hooks.run(hooks::Phase::PostStart, &hook_env)?;
+
+        let instance = instance::Instance {
+            id: crate::utils::sandbox_id().into(),
+            profile: args.config_file,
+            command,
+            bwrap_pid: proc_status.pid,
+            sandbox: proc_status.sandbox,
+            services: handles.iter().map(|(name, _)| name.clone()).collect(),
+            cgroup: proc.cgroup().map(Into::into),
+            owner_pid: std::process::id(),
+            seccomp_fd,
+            control: launch
+                .single_instance
+                .then(|| instance::control_path(crate::utils::sandbox_id())),
+            stop,
+            started: instance::now(),
+        };
+        let _control = match &instance.control {
+            Some(path) => Some(control::listen(&instance.id, path.clone())?),
+            None => None,
+        };
+        let _instance = instance.register()?;
+        drop(lock);
+
+        let mut report = proc.wait()?;
+        drop(app_output.capture);
+        stop_services(handles, &mut report);
+        log::info!("Sandbox exited: {:?}", report.exit);
+        report.usage.log();
+        if let Some(path) = &args.exit_report {
+            report.write(path)?;
+        }
+        let hook_env = hook_env.with_exit(&report.exit);
+        launch.hooks.run(hooks::Phase::PostExit, &hook_env)?;
+
+        Ok(report.exit.exit_code())
+    }
+
+    pub fn check(args: &CheckArgs) -> Result<bool, AppError> {
+        let problems = current_dir::run_in_dir(&args.config_dir, || {
+            Ok(check::check_profile(&args.config_dir, &args.config))
+        })?;
+
+        for it in &problems {
+            println!("[{}] {}", it.section, it.error);
+        }
+
+        if problems.is_empty() {
+            println!("Profile is valid");
+        } else {
+            println!("Found {} problem(s)", problems.len());
+        }
+
+        Ok(problems.is_empty())
+    }
+
+    pub fn ps() -> Result<(), AppError> {
+        let now = instance::now();
+        for it in instance::Instance::list()? {
+            println!(
+                "{}\t{}\t{}\t{}s\t{}",
+                it.id,
+                it.profile_name(),
+                it.sandbox.child_pid,
+                now.saturating_sub(it.started),
+                it.command.join(" ")
+            );
+        }
+        Ok(())
+    }
+
+    pub fn stop(id: &str) -> Result<(), AppError> {
+        instance::Instance::load(id)?.stop()
+    }
+
+    pub fn enter(id: &str, command: &[OsString]) -> Result<ExitCode, AppError> {
+        enter::run(&instance::Instance::load(id)?, command)
+    }
+
+    pub fn freeze(id: &str, frozen: bool) -> Result<(), AppError> {
+        use crate::system::cgroup;
+
+        let path = cgroup::sandbox_path(&cgroup::delegated_root()?, id)?;
+        cgroup::set_frozen(&path, frozen)
+    }
+
+    pub fn list_profiles() -> Result<(), AppError> {
+        for it in search_path::list()? {
+            let shadowed = if it.shadowed { " (shadowed)" } else { "" };
+            println!("{}\t{}{shadowed}", it.name.display(), it.path.display());
+        }
+        Ok(())
+    }
+}
+
+// Stopped explicitly to collect usage, before post_exit hooks see the sandbox gone
+fn stop_services(mut handles: Vec<(String, HandleType)>, report: &mut ExitReport) {
+    for (name, handle) in &mut handles {
+        if let Some(usage) = handle.stop() {
+            let name = name.clone();
+            report.usage.services.push(ServiceUsage { name, usage });
+        }
+    }
+}
+
+// Captures live until services are stopped
+fn capture_services<C: Context>(
+    output: &output::Output,
+    profile: &str,
+    services: &mut [Box<dyn Service<C>>],
+) -> Result<Vec<LogCapture>, AppError> {
+    let mut logs = Vec::new();
+    for it in services {
+        let name = it.name().to_owned();
+        let Some(command) = it.command_mut() else {
+            continue;
+        };
+        let captured = output.capture(profile, &name, output.services())?;
+        if let Some((stdout, stderr)) = captured.pipes {
+            command.stdout(stdout);
+            command.stderr(stderr);
+        }
+        logs.extend(captured.capture);
+    }
+    Ok(logs)
+}
 
-        let status = proc.wait()?;
-        Ok(status)
+// Services wait only for services they are ordered after, others start in parallel
+// Profile with overrides and policy, nothing is started or checked on the host yet
+fn load_config(args: &Args, policy: Option<&policy::Policy>) -> Result<config::Config, AppError> {
+    current_dir::run_in_dir(&args.config_dir, || {
+        let mut profile = profile::load(&args.config_dir, &args.config)?;
+        for it in &args.overrides {
+            it.apply(&mut profile)?;
+        }
+        if let Some(policy) = policy {
+            policy.apply(&mut profile);
+        }
+        Ok(profile.try_into()?)
+    })
+}
+
+fn start_services<C: Context>(
+    services: Vec<Box<dyn Service<C>>>,
+    info: BwrapInfo,
+    ready_timeouts: &[(&str, Duration)],
+) -> Result<Vec<(String, HandleType)>, AppError> {
+    let mut handles: Vec<(String, HandleType)> = Vec::new();
+    let mut pending = services;
+    while !pending.is_empty() {
+        // Sorted on load, so the first pending service is always ready
+        let (ready, rest): (Vec<_>, Vec<_>) = pending.into_iter().partition(|v| {
+            v.after()
+                .iter()
+                .all(|name| handles.iter().any(|h| h.0 == *name))
+        });
+        pending = rest;
+
+        let started = std::thread::scope(|scope| {
+            let threads: Vec<_> = ready
+                .into_iter()
+                .map(|v| scope.spawn(move || start_service(v, info, ready_timeouts)))
+                .collect();
+            let joined = threads.into_iter().map(|v| match v.join() {
+                Ok(started) => started,
+                Err(e) => std::panic::resume_unwind(e),
+            });
+            joined.collect::<Vec<_>>()
+        });
+        // Already started services are stopped on drop
+        for it in started {
+            handles.push(it?);
+        }
     }
+    Ok(handles)
+}
+
+fn start_service<C: Context>(
+    service: Box<dyn Service<C>>,
+    info: BwrapInfo,
+    ready_timeouts: &[(&str, Duration)],
+) -> Result<(String, HandleType), AppError> {
+    let name = service.name().to_owned();
+    log::info!("Starting '{name}' service");
+    let timeout = ready_timeouts
+        .iter()
+        .find(|v| services::is_instance_of(&name, v.0));
+    let info = match timeout {
+        Some((_, timeout)) => info.with_ready_timeout(*timeout),
+        None => info,
+    };
+    service.start(&info).map(|handle| (name, handle))
 }
@@ -0,0 +1,157 @@
+use crate::{
+    error::AppError,
+    system::{LogCapture, Rotation, Tee},
+    utils,
+};
+use serde::Deserialize;
+use std::io::PipeWriter;
+
+pub const OUTPUT_KEY: &str = "output";
+const APP_LOG: &str = "app";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    // Output goes where it went without [output] section
+    #[default]
+    Inherit,
+    File,
+    // Both the log file and the original destination
+    Tee,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Output {
+    #[serde(default)]
+    app: OutputMode,
+    // Helper services with own process, f.e. pasta or xdg-dbus-proxy
+    #[serde(default)]
+    services: OutputMode,
+    // Bytes
+    #[serde(default = "default_max_size")]
+    max_size: u64,
+    #[serde(default = "default_keep")]
+    keep: usize,
+    #[serde(default)]
+    timestamps: bool,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            app: OutputMode::default(),
+            services: OutputMode::default(),
+            max_size: default_max_size(),
+            keep: default_keep(),
+            timestamps: false,
+        }
+    }
+}
+
+fn default_max_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_keep() -> usize {
+    3
+}
+
+impl Output {
+    pub fn services(&self) -> OutputMode {
+        self.services
+    }
+
+    pub fn check(&self) -> Vec<AppError> {
+        let mut problems = Vec::new();
+        if self.max_size == 0 {
+            problems.push(AppError::Output("max_size must be positive".into()));
+        }
+        if self.app != OutputMode::Inherit || self.services != OutputMode::Inherit {
+            problems.extend(utils::state_dir().err());
+        }
+        problems
+    }
+
+    // Logs of every launch of the profile share the dir, files are appended
+    pub fn capture(
+        &self,
+        profile: &str,
+        name: &str,
+        mode: OutputMode,
+    ) -> Result<Captured, AppError> {
+        if mode == OutputMode::Inherit {
+            return Ok(Captured::default());
+        }
+        if let Some(problem) = self.check().into_iter().next() {
+            return Err(problem);
+        }
+
+        let dir = utils::state_dir()?.join("logs").join(profile);
+        std::fs::create_dir_all(&dir).map_err(AppError::file(&dir))?;
+        // Service names may have spaces, f.e. `pasta network`
+        let name: String = name
+            .chars()
+            .map(|v| if v.is_ascii_alphanumeric() { v } else { '-' })
+            .collect();
+        let path = dir.join(format!("{name}.log"));
+        log::info!("Output of {name} is written to {}", path.display());
+
+        let rotation = Rotation {
+            max_size: self.max_size,
+            keep: self.keep,
+        };
+        let mut capture = LogCapture::open(path, rotation, self.timestamps)?;
+        let tee = mode == OutputMode::Tee;
+        let stdout = capture.pipe(if tee { Tee::Stdout } else { Tee::Off })?;
+        let stderr = capture.pipe(if tee { Tee::Stderr } else { Tee::Off })?;
+        Ok(Captured {
+            pipes: Some((stdout, stderr)),
+            capture: Some(capture),
+        })
+    }
+
+    // Pseudoterminal relay owns the app output
+    pub fn capture_app(&self, profile: &str, pty: bool) -> Result<Captured, AppError> {
+        if pty && self.app != OutputMode::Inherit {
+            log::warn!("App output is not captured with --pty");
+            return Ok(Captured::default());
+        }
+        self.capture(profile, APP_LOG, self.app)
+    }
+}
+
+// Pipes go to the process, capture waits for the rest of output on drop
+#[derive(Debug, Default)]
+pub struct Captured {
+    pub pipes: Option<(PipeWriter, PipeWriter)>,
+    pub capture: Option<LogCapture>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output() {
+        let config: Output = toml::from_str(
+            r#"
+            app = "tee"
+            services = "file"
+            max_size = 1024
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.app, OutputMode::Tee);
+        assert_eq!(config.services(), OutputMode::File);
+        assert_eq!(config.keep, 3);
+        assert!(!config.timestamps);
+
+        let stdio = Output::default().capture_app("test", false).unwrap();
+        assert!(stdio.pipes.is_none());
+
+        let config: Output = toml::from_str("max_size = 0").unwrap();
+        assert_eq!(config.check().len(), 1);
+        assert!(toml::from_str::<Output>(r#"app = "syslog""#).is_err());
+    }
+}
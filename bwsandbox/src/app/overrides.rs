@@ -0,0 +1,211 @@
+use crate::error::AppError;
+use std::ffi::OsString;
+use toml::{Table, Value};
+
+#[derive(Debug, PartialEq)]
+pub enum Override {
+    // --set KEY=VALUE, bwrap template context value with env expansion
+    Context {
+        key: String,
+        value: String,
+    },
+    // --set-service SERVICE.FIELD[.FIELD]=VALUE, service config field
+    Service {
+        section: String,
+        path: Vec<String>,
+        value: Value,
+    },
+}
+
+impl Override {
+    pub fn context(arg: OsString) -> Result<Self, AppError> {
+        let (key, value) = split_arg(arg)?;
+        Ok(Self::Context { key, value })
+    }
+
+    pub fn service(arg: OsString) -> Result<Self, AppError> {
+        let (key, value) = split_arg(arg)?;
+        let mut path = key.split('.').map(String::from).collect::<Vec<_>>();
+        if path.len() < 2 || path.iter().any(String::is_empty) {
+            return Err(AppError::Override(key));
+        }
+
+        let section = path.remove(0);
+        Ok(Self::Service {
+            section,
+            path,
+            value: parse_value(value),
+        })
+    }
+
+    pub fn apply(&self, profile: &mut Table) -> Result<(), AppError> {
+        match self {
+            Self::Context { key, value } => {
+                let context = ["bwrap", "template"]
+                    .into_iter()
+                    .try_fold(&mut *profile, |table, key| get_table(table, key))
+                    .ok_or_else(|| {
+                        AppError::Override(format!("{key}: missing [bwrap.template]"))
+                    })?;
+                let context = context
+                    .entry("context")
+                    .or_insert_with(|| Table::new().into())
+                    .as_table_mut()
+                    .ok_or_else(|| AppError::Override(key.clone()))?;
+
+                let value = Table::from_iter([
+                    ("type".to_owned(), Value::from("env")),
+                    ("value".to_owned(), Value::from(value.as_str())),
+                ]);
+                context.insert(key.clone(), value.into());
+            }
+            Self::Service {
+                section,
+                path,
+                value,
+            } => {
+                let missing = || AppError::Override(format!("{section}: missing [{section}]"));
+                let (mut table, path) = match profile.get_mut(section) {
+                    Some(Value::Table(table)) => (table, path.as_slice()),
+                    // Several instances, `dbus.system.field` selects one by name
+                    Some(Value::Array(items)) if path.len() > 1 => {
+                        let (name, path) = path.split_first().expect("Checked above");
+                        let table = items
+                            .iter_mut()
+                            .filter_map(Value::as_table_mut)
+                            .find(|v| v.get("name").and_then(Value::as_str) == Some(name))
+                            .ok_or_else(|| {
+                                AppError::Override(format!("{section}.{name}: missing instance"))
+                            })?;
+                        (table, path)
+                    }
+                    _ => return Err(missing()),
+                };
+                // Include wasn't inlined, its fields would be ignored on load
+                if table.get("type").and_then(Value::as_str) == Some("include") {
+                    return Err(AppError::Override(format!(
+                        "{section}: include is not resolved"
+                    )));
+                }
+
+                let (field, parents) = path.split_last().expect("Validated on parse");
+                for it in parents {
+                    table = table
+                        .entry(it.as_str())
+                        .or_insert_with(|| Table::new().into())
+                        .as_table_mut()
+                        .ok_or_else(|| AppError::Override(format!("{section}.{it}")))?;
+                }
+                table.insert(field.clone(), value.clone());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn get_table<'a>(table: &'a mut Table, key: &str) -> Option<&'a mut Table> {
+    table.get_mut(key).and_then(Value::as_table_mut)
+}
+
+fn split_arg(arg: OsString) -> Result<(String, String), AppError> {
+    let arg = arg
+        .into_string()
+        .map_err(|v| AppError::Override(v.display().to_string()))?;
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.into(), value.into())),
+        _ => Err(AppError::Override(arg)),
+    }
+}
+
+// Valid toml values (bool, int, arrays, inline tables) used as is, everything else is a string
+fn parse_value(raw: String) -> Value {
+    let parsed = toml::from_str::<Table>(&format!("value = {raw}"));
+    match parsed.ok().and_then(|mut v| v.remove("value")) {
+        Some(value) => value,
+        None => Value::String(raw),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let v = Override::service("dbus.user_bus=$HOME/bus".into()).unwrap();
+        let expected = Override::Service {
+            section: "dbus".into(),
+            path: vec!["user_bus".into()],
+            value: Value::from("$HOME/bus"),
+        };
+        assert_eq!(v, expected);
+
+        let v = Override::service("pasta.quiet=false".into()).unwrap();
+        assert!(matches!(
+            v,
+            Override::Service {
+                value: Value::Boolean(false),
+                ..
+            }
+        ));
+
+        assert!(Override::service("pasta=false".into()).is_err());
+        assert!(Override::service("pasta..quiet=false".into()).is_err());
+        assert!(Override::context("=value".into()).is_err());
+        assert!(Override::context("key".into()).is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut profile = toml::toml! {
+            [bwrap.template]
+            name = "base.j2"
+            dir = "./templates"
+
+            [pasta]
+            type = "inline"
+        };
+
+        let v = Override::context("SANDBOXED_HOME=$HOME/sandbox/tmp".into()).unwrap();
+        v.apply(&mut profile).unwrap();
+        let value = &profile["bwrap"]["template"]["context"]["SANDBOXED_HOME"];
+        assert_eq!(value["type"].as_str(), Some("env"));
+        assert_eq!(value["value"].as_str(), Some("$HOME/sandbox/tmp"));
+
+        let v = Override::service("pasta.template.name=pasta.j2".into()).unwrap();
+        v.apply(&mut profile).unwrap();
+        assert_eq!(
+            profile["pasta"]["template"]["name"].as_str(),
+            Some("pasta.j2")
+        );
+
+        let v = Override::service("dbus.user_bus=/bus".into()).unwrap();
+        assert!(v.apply(&mut profile).is_err());
+
+        let mut profile = toml::toml! {
+            [[dbus]]
+            type = "inline"
+
+            [[dbus]]
+            type = "inline"
+            name = "system"
+        };
+        let v = Override::service("dbus.system.user_bus=/bus".into()).unwrap();
+        v.apply(&mut profile).unwrap();
+        assert_eq!(profile["dbus"][1]["user_bus"].as_str(), Some("/bus"));
+        assert!(profile["dbus"][0].get("user_bus").is_none());
+
+        let v = Override::service("dbus.session.user_bus=/bus".into()).unwrap();
+        assert!(v.apply(&mut profile).is_err());
+
+        let mut profile = toml::toml! {
+            [pasta]
+            type = "include"
+            include = "/not/exists/pasta.toml"
+        };
+        let v = Override::service("pasta.quiet=false".into()).unwrap();
+        assert!(v.apply(&mut profile).is_err());
+        assert!(profile["pasta"].get("quiet").is_none());
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{app::profile, bwrap::BwrapArgs, error::AppError};
+use serde::Deserialize;
+use std::{
+    fs::Metadata,
+    io::Read,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+use toml::Table;
+
+pub const POLICY_FILE_NAME: &str = "policy.toml";
+const POLICY_DIR: &str = "/etc/bwsandbox";
+const MAX_SIZE: u64 = 64 * 1024;
+
+// Host-wide floor for every profile, user can't remove it by editing own profiles
+#[derive(Debug, Deserialize, Default)]
+pub struct Policy {
+    // Merged over profile sections after overrides, so policy values win
+    #[serde(default)]
+    services: Table,
+    #[serde(default)]
+    bwrap: BwrapPolicy,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct BwrapPolicy {
+    #[serde(default)]
+    required_flags: Vec<String>,
+    // Host paths which can't be bound in any way, binds of parent dirs included
+    #[serde(default)]
+    deny_paths: Vec<PathBuf>,
+}
+
+impl Policy {
+    pub fn load() -> Result<Option<Self>, AppError> {
+        Self::from_file(&Path::new(POLICY_DIR).join(POLICY_FILE_NAME))
+    }
+
+    fn from_file(path: &Path) -> Result<Option<Self>, AppError> {
+        let file = match std::fs::File::open(path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(AppError::file(path)(e)),
+        };
+
+        let meta = file.metadata().map_err(AppError::file(path))?;
+        check_permissions(path, &meta)?;
+
+        let mut content = String::new();
+        file.take(MAX_SIZE)
+            .read_to_string(&mut content)
+            .map_err(AppError::file(path))?;
+
+        let mut policy: Self = crate::utils::deserialize(&content)?;
+        policy.validate()?;
+        let dir = path.parent().expect("Missing policy dir?");
+        profile::resolve_paths(dir, &mut policy.services);
+
+        log::info!("Host policy loaded: {}", path.display());
+        Ok(Some(policy))
+    }
+
+    fn validate(&self) -> Result<(), AppError> {
+        let relative = self.bwrap.deny_paths.iter().find(|v| !v.is_absolute());
+        if let Some(path) = relative {
+            let reason = format!("deny path {} must be absolute", path.display());
+            return Err(AppError::PolicyInvalid(reason));
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, profile: &mut Table) {
+        profile::merge(profile, self.services.clone());
+    }
+
+    // Every violation logged, launch rejected if any found
+    pub fn check(&self, args: &BwrapArgs) -> Result<(), AppError> {
+        let violations = self.violations(args);
+        for it in &violations {
+            log::error!("[policy] {it}");
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Policy(violations.len()))
+        }
+    }
+
+    fn violations(&self, args: &BwrapArgs) -> Vec<String> {
+        let mut found = Vec::new();
+        for flag in &self.bwrap.required_flags {
+            if !args.has_flag(flag) {
+                found.push(format!("required bwrap flag {flag} is missing"));
+            }
+        }
+
+        let deny_paths = self
+            .bwrap
+            .deny_paths
+            .iter()
+            .map(|v| v.canonicalize().unwrap_or_else(|_| v.clone()))
+            .collect::<Vec<_>>();
+
+        for (arg, src) in args
+            .iter()
+            .flat_map(|v| v.host_sources().map(move |src| (v, src)))
+        {
+            let src = src.canonicalize().unwrap_or_else(|_| src.into());
+            // Denied path itself, one of its parents or anything below it
+            let denied = deny_paths
+                .iter()
+                .find(|v| v.starts_with(&src) || src.starts_with(v));
+            if let Some(denied) = denied {
+                found.push(format!(
+                    "{} exposes denied path {}",
+                    arg.name().display(),
+                    denied.display()
+                ));
+            }
+        }
+        found
+    }
+}
+
+// Same rules as bwsandbox-netns config: root owned, not writable by others, limited size
+fn check_permissions(path: &Path, meta: &Metadata) -> Result<(), AppError> {
+    if meta.uid() != 0 || meta.gid() != 0 || meta.mode() & 0o022 != 0 {
+        return Err(AppError::PolicyPermissions(path.into()));
+    }
+    if meta.size() >= MAX_SIZE {
+        let reason = format!("file is bigger than {MAX_SIZE} bytes");
+        return Err(AppError::PolicyInvalid(reason));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn parse(args: &[&str]) -> BwrapArgs {
+        BwrapArgs::parse(args.iter().map(OsString::from).collect()).unwrap()
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy: Policy = toml::from_str(
+            r#"
+            [services.env_mapper]
+            type = "inline"
+            unset_all = true
+
+            [bwrap]
+            required_flags = ["--die-with-parent", "--new-session", "--unshare-pid"]
+            deny_paths = ["/etc/shadow", "/root"]
+            "#,
+        )
+        .unwrap();
+
+        let mut profile = toml::toml! {
+            [env_mapper]
+            type = "inline"
+            unset_all = false
+            keep = ["HOME"]
+        };
+        policy.apply(&mut profile);
+        assert_eq!(profile["env_mapper"]["unset_all"].as_bool(), Some(true));
+        assert!(profile["env_mapper"]["keep"].is_array());
+
+        let args = parse(&[
+            "--unshare-all",
+            "--die-with-parent",
+            "--new-session",
+            "--ro-bind",
+            "/usr",
+            "/usr",
+        ]);
+        assert!(policy.violations(&args).is_empty());
+
+        let args = parse(&["--die-with-parent", "--ro-bind", "/etc", "/etc"]);
+        assert_eq!(policy.violations(&args).len(), 3);
+
+        let policy = Policy {
+            bwrap: BwrapPolicy {
+                deny_paths: vec!["relative".into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(policy.validate().is_err());
+    }
+
+    #[test]
+    fn test_deny_inside() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("id_ed25519");
+        std::fs::write(&file, "").unwrap();
+        let policy = Policy {
+            bwrap: BwrapPolicy {
+                deny_paths: vec![dir.path().into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let file = file.to_str().unwrap();
+        let args = parse(&["--ro-bind", file, "/x"]);
+        assert_eq!(policy.violations(&args).len(), 1);
+        let args = parse(&["--ro-bind", "/usr", "/usr"]);
+        assert!(policy.violations(&args).is_empty());
+    }
+
+    #[test]
+    fn test_permissions() {
+        let path = std::env::temp_dir().join(format!("policy-{}.toml", crate::utils::rand_id(8)));
+        std::fs::write(&path, "").unwrap();
+
+        let meta = std::fs::metadata(&path).unwrap();
+        let is_root = meta.uid() == 0 && meta.gid() == 0;
+        assert_eq!(check_permissions(&path, &meta).is_ok(), is_root);
+
+        // World writable file rejected for any owner
+        let mut perms = meta.permissions();
+        std::os::unix::fs::PermissionsExt::set_mode(&mut perms, 0o666);
+        std::fs::set_permissions(&path, perms).unwrap();
+        let meta = std::fs::metadata(&path).unwrap();
+        assert!(check_permissions(&path, &meta).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,380 @@
+use crate::{config::EnvVal, error::AppError, utils};
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+const EXTENDS_KEY: &str = "extends";
+const REMOVE_KEY: &str = "remove";
+
+// Merge rules, applied from the first parent to the profile itself:
+// - tables merged recursively
+// - arrays appended, parent items first
+// - array tables with the same `name`, f.e. `[[dbus]]` instances, merged as tables
+// - scalars and mismatched types overridden
+// - `remove = ["section"]` drops inherited section before profile own sections merged
+// - parent shared by several parents (diamond) merged once, on its first use
+pub fn load(dir: &Path, content: &str) -> Result<Table, AppError> {
+    let mut visited = Vec::new();
+    let mut loaded = Vec::new();
+    load_nested(dir, content, &mut visited, &mut loaded)
+}
+
+fn load_nested(
+    dir: &Path,
+    content: &str,
+    visited: &mut Vec<PathBuf>,
+    loaded: &mut Vec<PathBuf>,
+) -> Result<Table, AppError> {
+    let mut table: Table = utils::deserialize(content)?;
+    resolve_paths(dir, &mut table);
+
+    let extends = take_names(&mut table, EXTENDS_KEY)?;
+    let remove = take_names(&mut table, REMOVE_KEY)?;
+
+    let mut merged = Table::new();
+    for name in extends {
+        let (path, content) = find_parent(dir, &name)?;
+        if visited.contains(&path) {
+            return Err(AppError::ProfileCycle(path));
+        }
+        if loaded.contains(&path) {
+            log::trace!("Already extended with {}", path.display());
+            continue;
+        }
+
+        visited.push(path.clone());
+        let parent_dir = path.parent().expect("Missing profile dir?");
+        let parent = load_nested(parent_dir, &content, visited, loaded)?;
+        visited.pop();
+        loaded.push(path.clone());
+
+        log::trace!("Extending profile with {}", path.display());
+        merge(&mut merged, parent);
+    }
+
+    for it in &remove {
+        merged.remove(it);
+    }
+
+    merge(&mut merged, table);
+    Ok(merged)
+}
+
+fn take_names(table: &mut Table, key: &'static str) -> Result<Vec<String>, AppError> {
+    let Some(value) = table.remove(key) else {
+        return Ok(Vec::new());
+    };
+    let names = value.try_into()?;
+    Ok(names)
+}
+
+// Parent profile searched next to the child first, then in default profiles dir
+fn find_parent(dir: &Path, name: &str) -> Result<(PathBuf, String), AppError> {
+    let file = format!("{name}.toml");
+    let path = dir.join(&file);
+    let (path, content) = if path.is_file() {
+        let content = std::fs::read_to_string(&path).map_err(AppError::file(&path))?;
+        (path, content)
+    } else {
+        super::args::from_name(file.as_ref())?
+    };
+
+    let path = path.canonicalize().map_err(AppError::file(&path))?;
+    Ok((path, content))
+}
+
+pub fn merge(base: &mut Table, other: Table) {
+    for (key, value) in other {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(other)) => merge(base, other),
+            (Some(Value::Array(base)), Value::Array(other)) => merge_array(base, other),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn merge_array(base: &mut Vec<Value>, other: Vec<Value>) {
+    let name = |v: &Value| v.get("name").and_then(Value::as_str).map(String::from);
+    for value in other {
+        let same = name(&value)
+            .and_then(|other| base.iter_mut().find(|v| name(v).as_ref() == Some(&other)));
+        match (same, value) {
+            (Some(Value::Table(base)), Value::Table(other)) => merge(base, other),
+            (_, value) => base.push(value),
+        }
+    }
+}
+
+// Relative paths in parent profiles must stay relative to the parent profile dir
+// Includes also inlined to allow merge their content with other profiles
+pub fn resolve_paths(dir: &Path, table: &mut Table) {
+    // Services with several instances are arrays of tables
+    let sections = table.iter_mut().flat_map(|(_, v)| match v {
+        Value::Array(items) => items.iter_mut().filter_map(Value::as_table_mut).collect(),
+        v => v.as_table_mut().into_iter().collect::<Vec<_>>(),
+    });
+    for section in sections {
+        resolve_include(dir, section);
+    }
+    resolve_templates(dir, table);
+
+    let mounts = table.get_mut("bwrap").and_then(|v| v.get_mut("mounts"));
+    if let Some(mounts) = mounts.and_then(Value::as_array_mut) {
+        resolve_mounts(dir, mounts);
+    }
+}
+
+// Symlink source is a path inside sandbox, so only host paths resolved
+fn resolve_mounts(dir: &Path, mounts: &mut [Value]) {
+    for mount in mounts.iter_mut().filter_map(Value::as_table_mut) {
+        let is_symlink = mount.get("type").and_then(Value::as_str) == Some("symlink");
+        for (key, value) in mount.iter_mut() {
+            match key.as_str() {
+                "src" if !is_symlink => resolve_path(dir, value),
+                "rw_src" | "work_dir" => resolve_path(dir, value),
+                "sources" => value
+                    .as_array_mut()
+                    .into_iter()
+                    .flatten()
+                    .for_each(|v| resolve_path(dir, v)),
+                _ => {}
+            }
+        }
+    }
+}
+
+// Every command template at any depth, also ones inlined from includes
+fn resolve_templates(dir: &Path, table: &mut Table) {
+    for (key, value) in table.iter_mut() {
+        match value {
+            Value::Table(template) if key == "template" => {
+                if let Some(template_dir) = template.get_mut("dir") {
+                    resolve_path(dir, template_dir);
+                }
+            }
+            Value::Table(nested) => resolve_templates(dir, nested),
+            Value::Array(items) => items
+                .iter_mut()
+                .filter_map(Value::as_table_mut)
+                .for_each(|v| resolve_templates(dir, v)),
+            _ => {}
+        }
+    }
+}
+
+fn resolve_include(dir: &Path, section: &mut Table) {
+    if section.get("type").and_then(Value::as_str) != Some("include") {
+        return;
+    }
+
+    let Some(include) = section.get_mut("include") else {
+        return;
+    };
+    resolve_path(dir, include);
+
+    // Keep entry as is on any error, it will be reported by Entry::load with proper context
+    let Some(path) = include.as_str() else {
+        return;
+    };
+    let included = std::fs::read_to_string(path)
+        .map_err(AppError::file(path))
+        .and_then(|v| Ok(utils::deserialize::<Table>(&v)?));
+
+    match included {
+        Ok(mut included) => {
+            log::trace!("Inlined include: {path}");
+            // Keys next to include, f.e. `after`, are kept
+            section.remove("include");
+            included.extend(std::mem::take(section));
+            included.insert("type".into(), "inline".into());
+            *section = included;
+        }
+        Err(e) => log::warn!("Include {path} is not inlined: {e}"),
+    }
+}
+
+fn resolve_path(dir: &Path, value: &mut Value) {
+    let Some(path) = value.as_str() else {
+        return;
+    };
+    let Ok(path) = EnvVal::<PathBuf>::resolve(path.into()) else {
+        return;
+    };
+    // Expanded value is stored also for absolute paths, includes are read from it
+    let path = path.into_inner();
+    let path = if path.is_relative() {
+        dir.join(path)
+    } else {
+        path
+    };
+    *value = path.to_string_lossy().into_owned().into();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let mut base = toml::toml! {
+            [bwrap]
+            inline = [{ type = "str", value = "--tmpfs" }, { type = "str", value = "/home" }]
+
+            [bwrap.template]
+            name = "base.j2"
+            dir = "/base"
+
+            [env_mapper]
+            type = "inline"
+            unset_all = true
+        };
+
+        let child = toml::toml! {
+            [bwrap]
+            inline = [{ type = "str", value = "--new-session" }]
+
+            [bwrap.template]
+            name = "child.j2"
+
+            [env_mapper]
+            unset_all = false
+        };
+
+        merge(&mut base, child);
+        let expected = toml::toml! {
+            [bwrap]
+            inline = [
+                { type = "str", value = "--tmpfs" },
+                { type = "str", value = "/home" },
+                { type = "str", value = "--new-session" },
+            ]
+
+            [bwrap.template]
+            name = "child.j2"
+            dir = "/base"
+
+            [env_mapper]
+            type = "inline"
+            unset_all = false
+        };
+        assert_eq!(base, expected);
+    }
+
+    #[test]
+    fn test_merge_named() {
+        let mut base = toml::toml! {
+            [[dbus]]
+            user_bus = "/session"
+
+            [[dbus]]
+            name = "system"
+            user_bus = "/system"
+            talk = ["org.freedesktop.NetworkManager"]
+        };
+
+        let child = toml::toml! {
+            [[dbus]]
+            name = "system"
+            talk = ["org.freedesktop.UPower"]
+
+            [[dbus]]
+            name = "a11y"
+        };
+
+        merge(&mut base, child);
+        let expected = toml::toml! {
+            [[dbus]]
+            user_bus = "/session"
+
+            [[dbus]]
+            name = "system"
+            user_bus = "/system"
+            talk = ["org.freedesktop.NetworkManager", "org.freedesktop.UPower"]
+
+            [[dbus]]
+            name = "a11y"
+        };
+        assert_eq!(base, expected);
+    }
+
+    #[test]
+    fn test_diamond() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &str| {
+            std::fs::write(dir.path().join(format!("{name}.toml")), content).unwrap();
+        };
+        write(
+            "a",
+            "[bwrap]\ninline = [{ type = \"str\", value = \"--unshare-all\" }]",
+        );
+        write("b", "extends = [\"a\"]");
+        write("c", "extends = [\"a\"]\n[[process]]\nname = \"c\"");
+
+        let merged = load(dir.path(), "extends = [\"b\", \"c\"]").unwrap();
+        assert_eq!(merged["bwrap"]["inline"].as_array().unwrap().len(), 1);
+        assert_eq!(merged["process"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_paths() {
+        let mut table = toml::toml! {
+            [bwrap.template]
+            name = "base.j2"
+            dir = "./templates"
+
+            [[bwrap.mounts]]
+            type = "ro-bind"
+            src = "./data"
+
+            [[bwrap.mounts]]
+            type = "symlink"
+            src = "usr/lib"
+            dest = "/lib"
+
+            [dbus]
+            type = "include"
+            include = "/not/exists/dbus.toml"
+        };
+
+        resolve_paths(Path::new("/profiles"), &mut table);
+        assert_eq!(
+            table["bwrap"]["template"]["dir"].as_str(),
+            Some("/profiles/./templates")
+        );
+        assert_eq!(
+            table["bwrap"]["mounts"][0]["src"].as_str(),
+            Some("/profiles/./data")
+        );
+        assert_eq!(table["bwrap"]["mounts"][1]["src"].as_str(), Some("usr/lib"));
+        assert_eq!(table["dbus"]["type"].as_str(), Some("include"));
+    }
+
+    #[test]
+    fn test_resolve_nested_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let include = "[template]\nname = \"dbus.j2\"\ndir = \"./templates\"";
+        std::fs::write(dir.path().join("dbus.toml"), include).unwrap();
+
+        let mut table = toml::toml! {
+            [[dbus]]
+            name = "system"
+            type = "include"
+            include = "./dbus.toml"
+
+            [process.extra.template]
+            name = "process.j2"
+            dir = "./templates"
+        };
+
+        resolve_paths(dir.path(), &mut table);
+        let templates = dir.path().join("./templates");
+        let templates = templates.to_str();
+        assert_eq!(table["dbus"][0]["type"].as_str(), Some("inline"));
+        assert_eq!(table["dbus"][0]["template"]["dir"].as_str(), templates);
+        assert_eq!(
+            table["process"]["extra"]["template"]["dir"].as_str(),
+            templates
+        );
+    }
+}
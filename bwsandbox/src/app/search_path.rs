@@ -0,0 +1,163 @@
+use crate::{
+    app::{auto, policy},
+    error::AppError,
+    utils,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+};
+use toml::Table;
+
+const CONFIG_DIRS: &str = "XDG_CONFIG_DIRS";
+const CONFIG_DIRS_DEFAULT: &str = "/etc/xdg";
+const SYSTEM_DIRS: [&str; 2] = ["/etc/bwsandbox", "/usr/share/bwsandbox/profiles"];
+// Config files which share dirs with profiles
+const RESERVED: [&str; 2] = [auto::AUTO_FILE, policy::POLICY_FILE_NAME];
+
+fn get_config_dir() -> Result<String, AppError> {
+    const CONFIG_DIR: &str = "XDG_CONFIG_HOME";
+    const HOME: &str = "HOME";
+
+    match std::env::var(CONFIG_DIR) {
+        Ok(v) if !v.is_empty() => Ok(v),
+        Err(err @ std::env::VarError::NotUnicode(_)) => {
+            //
+            Err(AppError::Env(CONFIG_DIR.into(), err))
+        }
+        // Handle both scenarios: NotPreseted and presented but empty
+        _ => {
+            let home = std::env::var(HOME).map_err(AppError::env(HOME))?;
+            Ok(format!("{home}/.config"))
+        }
+    }
+}
+
+// Ordered by priority: user config dir, $XDG_CONFIG_DIRS entries, system wide dirs
+// The first found profile wins, so user can shadow admin managed profiles
+pub fn dirs() -> Result<Vec<PathBuf>, AppError> {
+    let mut dirs = vec![PathBuf::from(get_config_dir()?).join(utils::APP_NAME)];
+
+    let config_dirs = std::env::var_os(CONFIG_DIRS).filter(|v| !v.is_empty());
+    let config_dirs = config_dirs.unwrap_or_else(|| CONFIG_DIRS_DEFAULT.into());
+    dirs.extend(
+        std::env::split_paths(&config_dirs)
+            // Relative paths are invalid by XDG spec
+            .filter(|v| v.is_absolute())
+            .map(|v| v.join(utils::APP_NAME)),
+    );
+    dirs.extend(SYSTEM_DIRS.map(PathBuf::from));
+
+    let mut unique = Vec::with_capacity(dirs.len());
+    for it in dirs {
+        if !unique.contains(&it) {
+            unique.push(it);
+        }
+    }
+    Ok(unique)
+}
+
+pub fn find(file: &OsStr) -> Result<PathBuf, AppError> {
+    let dirs = dirs()?;
+    let found = dirs.iter().map(|v| v.join(file)).find(|v| v.is_file());
+    found.ok_or_else(|| AppError::ProfileNotFound(file.into(), dirs))
+}
+
+pub fn find_first(file: &OsStr) -> Result<Option<PathBuf>, AppError> {
+    let mut found = dirs()?.into_iter().map(|v| v.join(file));
+    Ok(found.find(|v| v.is_file()))
+}
+
+#[derive(Debug)]
+pub struct FoundProfile {
+    pub name: OsString,
+    pub path: PathBuf,
+    // Same name found earlier in the search path
+    pub shadowed: bool,
+}
+
+pub fn list() -> Result<Vec<FoundProfile>, AppError> {
+    Ok(list_in(&dirs()?))
+}
+
+// Include fragments share dirs with profiles, profile has own or inherited [bwrap]
+fn is_profile(path: &Path) -> bool {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return false;
+    };
+    // Broken profile is still listed, error is shown on launch
+    utils::deserialize::<Table>(&content).map_or(true, |v| {
+        v.contains_key("bwrap") || v.contains_key("extends")
+    })
+}
+
+fn list_in(dirs: &[PathBuf]) -> Vec<FoundProfile> {
+    let mut found: Vec<FoundProfile> = Vec::new();
+    for dir in dirs {
+        // Missing or unreadable dirs are ordinary, most of them are optional
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+
+        let mut profiles = entries
+            .filter_map(Result::ok)
+            .map(|v| v.path())
+            .filter(|v| v.is_file() && v.extension() == Some(OsStr::new("toml")))
+            .filter(|v| {
+                !RESERVED
+                    .iter()
+                    .any(|r| v.file_name() == Some(OsStr::new(r)))
+            })
+            .filter(|v| is_profile(v))
+            .collect::<Vec<_>>();
+        profiles.sort();
+
+        for path in profiles {
+            let name = path.file_stem().expect("Filtered by extension").to_owned();
+            let shadowed = found.iter().any(|v| v.name == name);
+            found.push(FoundProfile {
+                name,
+                path,
+                shadowed,
+            });
+        }
+    }
+
+    // Stable sort keeps search path order for the same name
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_in() {
+        let root = tempfile::tempdir().unwrap();
+        let user = root.path().join("user");
+        let system = root.path().join("system");
+        let profile = "[bwrap]\ninline = []\n";
+        for dir in [&user, &system] {
+            std::fs::create_dir(dir).unwrap();
+            std::fs::write(dir.join("shared.toml"), profile).unwrap();
+        }
+        std::fs::write(system.join("system-only.toml"), profile).unwrap();
+        std::fs::write(system.join("child.toml"), "extends = [\"shared\"]").unwrap();
+        std::fs::write(system.join("fragment.toml"), "user_bus = \"/bus\"").unwrap();
+        std::fs::write(system.join(auto::AUTO_FILE), profile).unwrap();
+
+        let found = list_in(&[user.clone(), system.clone(), root.path().join("missing")]);
+        let found = found
+            .iter()
+            .map(|v| (v.name.to_str().unwrap(), v.path.clone(), v.shadowed))
+            .collect::<Vec<_>>();
+        let expected = [
+            ("child", system.join("child.toml"), false),
+            ("shared", user.join("shared.toml"), false),
+            ("shared", system.join("shared.toml"), true),
+            ("system-only", system.join("system-only.toml"), false),
+        ];
+        assert_eq!(found, expected);
+    }
+}
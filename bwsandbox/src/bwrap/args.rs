@@ -0,0 +1,420 @@
+use crate::error::AppError;
+use std::{
+    ffi::{OsStr, OsString},
+    os::fd::{AsFd, AsRawFd},
+    path::Path,
+};
+
+// Number of values for every bwrap option, `bwrap --help` for 0.11
+fn arity(name: &str) -> Option<usize> {
+    let arity = match name {
+        "--unshare-all"
+        | "--share-net"
+        | "--unshare-user"
+        | "--unshare-user-try"
+        | "--unshare-ipc"
+        | "--unshare-pid"
+        | "--unshare-net"
+        | "--unshare-uts"
+        | "--unshare-cgroup"
+        | "--unshare-cgroup-try"
+        | "--disable-userns"
+        | "--assert-userns-disabled"
+        | "--clearenv"
+        | "--new-session"
+        | "--die-with-parent"
+        | "--as-pid-1"
+        | "--level-prefix" => 0,
+        "--args" | "--argv0" | "--userns" | "--userns2" | "--pidns" | "--uid" | "--gid"
+        | "--hostname" | "--chdir" | "--unsetenv" | "--lock-file" | "--sync-fd"
+        | "--remount-ro" | "--overlay-src" | "--tmp-overlay" | "--ro-overlay" | "--exec-label"
+        | "--file-label" | "--proc" | "--dev" | "--tmpfs" | "--mqueue" | "--dir" | "--seccomp"
+        | "--add-seccomp-fd" | "--block-fd" | "--userns-block-fd" | "--info-fd"
+        | "--json-status-fd" | "--cap-add" | "--cap-drop" | "--perms" | "--size" => 1,
+        "--setenv" | "--bind" | "--bind-try" | "--dev-bind" | "--dev-bind-try" | "--ro-bind"
+        | "--ro-bind-try" | "--bind-fd" | "--ro-bind-fd" | "--file" | "--bind-data"
+        | "--ro-bind-data" | "--symlink" | "--chmod" => 2,
+        "--overlay" => 3,
+        _ => return None,
+    };
+    Some(arity)
+}
+
+// Options which change the next option, kept and skipped together with it
+fn is_modifier(name: &str) -> bool {
+    matches!(name, "--perms" | "--size" | "--overlay-src")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Namespace {
+    User,
+    Ipc,
+    Pid,
+    Net,
+    Uts,
+    Cgroup,
+}
+
+impl Namespace {
+    const ALL: [Self; 6] = [
+        Self::User,
+        Self::Ipc,
+        Self::Pid,
+        Self::Net,
+        Self::Uts,
+        Self::Cgroup,
+    ];
+
+    fn unshare_flags(self) -> &'static [&'static str] {
+        match self {
+            Self::User => &["--unshare-user", "--unshare-user-try"],
+            Self::Ipc => &["--unshare-ipc"],
+            Self::Pid => &["--unshare-pid"],
+            Self::Net => &["--unshare-net"],
+            Self::Uts => &["--unshare-uts"],
+            Self::Cgroup => &["--unshare-cgroup", "--unshare-cgroup-try"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BwrapArg {
+    name: OsString,
+    values: Vec<OsString>,
+}
+
+impl BwrapArg {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            values: Vec::new(),
+        }
+    }
+
+    pub fn arg(mut self, value: impl AsRef<OsStr>) -> Self {
+        self.values.push(value.as_ref().into());
+        self
+    }
+
+    // Fd must be passed to Context::inherit_fd, same as Command::inherit_fd
+    pub fn arg_fd(self, fd: &impl AsFd) -> Self {
+        self.arg(fd.as_fd().as_raw_fd().to_string())
+    }
+
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    pub fn values(&self) -> &[OsString] {
+        &self.values
+    }
+
+    pub fn is(&self, name: &str) -> bool {
+        self.name == name
+    }
+
+    // Path inside sandbox affected by option, the last one wins
+    pub fn mount_dest(&self) -> Option<&Path> {
+        let name = self.name.to_str()?;
+        let dest = match name {
+            "--bind" | "--bind-try" | "--dev-bind" | "--dev-bind-try" | "--ro-bind"
+            | "--ro-bind-try" | "--bind-fd" | "--ro-bind-fd" | "--file" | "--bind-data"
+            | "--ro-bind-data" | "--symlink" | "--overlay" | "--tmp-overlay" | "--ro-overlay"
+            | "--proc" | "--dev" | "--tmpfs" | "--mqueue" | "--dir" => self.values.last()?,
+            _ => return None,
+        };
+        Some(Path::new(dest))
+    }
+
+    // Host paths exposed into sandbox
+    pub fn host_sources(&self) -> impl Iterator<Item = &Path> {
+        let count = match self.name.to_str() {
+            Some(
+                "--bind" | "--bind-try" | "--dev-bind" | "--dev-bind-try" | "--ro-bind"
+                | "--ro-bind-try" | "--overlay-src",
+            ) => 1,
+            Some("--overlay") => 2,
+            _ => 0,
+        };
+        self.values.iter().take(count).map(Path::new)
+    }
+
+    fn env_key(&self) -> Option<&OsStr> {
+        match self.name.to_str()? {
+            "--setenv" | "--unsetenv" => self.values.first().map(OsString::as_os_str),
+            _ => None,
+        }
+    }
+
+    fn into_iter(self) -> impl Iterator<Item = OsString> {
+        std::iter::once(self.name).chain(self.values)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BwrapArgs {
+    items: Vec<BwrapArg>,
+    // New args inserted here, so services can put args before or after profile args
+    cursor: usize,
+    // Pushed modifiers wait for the option they belong to
+    modifiers: Vec<BwrapArg>,
+}
+
+impl BwrapArgs {
+    pub fn parse(args: Vec<OsString>) -> Result<Self, AppError> {
+        let mut items = Vec::new();
+        let mut iter = args.into_iter();
+        while let Some(name) = iter.next() {
+            // Unknown options kept as is without values, bwrap will report them
+            let arity = name.to_str().and_then(arity).unwrap_or(0);
+            let values = iter.by_ref().take(arity).collect::<Vec<_>>();
+            if values.len() != arity {
+                let name = name.to_string_lossy().into_owned();
+                return Err(AppError::BwrapArgs(name));
+            }
+            items.push(BwrapArg { name, values });
+        }
+
+        Ok(Self {
+            items,
+            cursor: 0,
+            modifiers: Vec::new(),
+        })
+    }
+
+    // Following pushes go after all existing args
+    pub fn append_to_end(&mut self) {
+        self.flush_modifiers();
+        self.cursor = self.items.len();
+    }
+
+    // Returns false if arg skipped as duplicate, modifiers are decided with the next option
+    pub fn push(&mut self, arg: BwrapArg) -> bool {
+        if arg.name.to_str().is_some_and(is_modifier) {
+            self.modifiers.push(arg);
+            return true;
+        }
+        if self.is_duplicate(&arg) {
+            log::debug!("Skip duplicated bwrap arg: {}", arg.name.display());
+            self.modifiers.clear();
+            return false;
+        }
+
+        self.flush_modifiers();
+        self.items.insert(self.cursor, arg);
+        self.cursor += 1;
+        true
+    }
+
+    // Modifiers without option are passed as is, bwrap will report them
+    fn flush_modifiers(&mut self) {
+        for it in std::mem::take(&mut self.modifiers) {
+            self.items.insert(self.cursor, it);
+            self.cursor += 1;
+        }
+    }
+
+    fn is_duplicate(&self, arg: &BwrapArg) -> bool {
+        let Some(name) = arg.name.to_str() else {
+            return false;
+        };
+
+        if let Some(ns) = Namespace::ALL
+            .into_iter()
+            .find(|v| v.unshare_flags().contains(&name))
+        {
+            return self.is_unshared(ns);
+        }
+
+        if let Some(dest) = arg.mount_dest() {
+            return self.is_mounted(arg, dest);
+        }
+        if let Some(key) = arg.env_key() {
+            let last = self.items.iter().rfind(|v| v.env_key() == Some(key));
+            return last == Some(arg);
+        }
+        if arg.values.is_empty() {
+            return self.items.iter().any(|v| v == arg);
+        }
+        false
+    }
+
+    // Same mount with same modifiers is the last one at dest, and nothing mounted over or
+    // under dest between it and the new one, f.e. --tmpfs on parent hides the earlier bind
+    fn is_mounted(&self, arg: &BwrapArg, dest: &Path) -> bool {
+        let Some(index) = self
+            .items
+            .iter()
+            .rposition(|v| v.mount_dest() == Some(dest))
+        else {
+            return false;
+        };
+        if self.items[index] != *arg || self.modifiers_of(index) != self.modifiers.as_slice() {
+            return false;
+        }
+
+        let between = if index < self.cursor {
+            index + 1..self.cursor
+        } else {
+            self.cursor..index
+        };
+        !self.items[between].iter().any(|v| {
+            v.mount_dest()
+                .is_some_and(|v| v.starts_with(dest) || dest.starts_with(v))
+        })
+    }
+
+    fn modifiers_of(&self, index: usize) -> &[BwrapArg] {
+        let count = self.items[..index]
+            .iter()
+            .rev()
+            .take_while(|v| v.name.to_str().is_some_and(is_modifier))
+            .count();
+        &self.items[index - count..index]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BwrapArg> {
+        self.items.iter()
+    }
+
+    // Options evaluated in order, same as bwrap does
+    pub fn is_unshared(&self, ns: Namespace) -> bool {
+        let mut unshared = false;
+        for it in &self.items {
+            if it.is("--unshare-all") || ns.unshare_flags().iter().any(|v| it.is(v)) {
+                unshared = true;
+            } else if ns == Namespace::Net && it.is("--share-net") {
+                unshared = false;
+            }
+        }
+        unshared
+    }
+
+    // Unshare flags are also satisfied by --unshare-all
+    pub fn has_flag(&self, name: &str) -> bool {
+        let ns = Namespace::ALL
+            .into_iter()
+            .find(|v| v.unshare_flags().contains(&name));
+        match ns {
+            Some(ns) => self.is_unshared(ns),
+            None => self.items.iter().any(|v| v.is(name)),
+        }
+    }
+
+    // Every occurrence is dropped, f.e. flag which breaks an option of the app
+    pub fn remove(&mut self, name: &str) {
+        let before = self.items[..self.cursor]
+            .iter()
+            .filter(|v| v.is(name))
+            .count();
+        self.items.retain(|v| !v.is(name));
+        self.cursor -= before;
+    }
+
+    pub fn into_args(mut self) -> impl Iterator<Item = OsString> {
+        self.flush_modifiers();
+        self.items.into_iter().flat_map(BwrapArg::into_iter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> BwrapArgs {
+        BwrapArgs::parse(args.iter().map(OsString::from).collect()).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let args = parse(&[
+            "--unshare-all",
+            "--share-net",
+            "--dev",
+            "/dev",
+            "--ro-bind",
+            "/usr",
+            "/usr",
+            "--tmpfs",
+            "/home",
+        ]);
+        assert!(!args.is_unshared(Namespace::Net));
+        assert!(args.is_unshared(Namespace::Pid));
+        let usr = args
+            .iter()
+            .find(|v| v.mount_dest() == Some(Path::new("/usr")));
+        assert_eq!(
+            usr,
+            Some(&BwrapArg::new("--ro-bind").arg("/usr").arg("/usr"))
+        );
+
+        let args = BwrapArgs::parse(vec!["--ro-bind".into(), "/usr".into()]);
+        assert!(args.is_err());
+    }
+
+    #[test]
+    fn test_push() {
+        let mut args = parse(&["--unshare-all", "--tmpfs", "/home", "--ro-bind", "/a", "/a"]);
+        args.push(BwrapArg::new("--bind").arg("/tmp").arg("/tmp"));
+        args.append_to_end();
+
+        assert!(!args.push(BwrapArg::new("--unshare-net")));
+        assert!(!args.push(BwrapArg::new("--ro-bind").arg("/a").arg("/a")));
+        assert!(args.push(BwrapArg::new("--share-net")));
+        assert!(args.push(BwrapArg::new("--unshare-net")));
+        assert!(args.push(BwrapArg::new("--perms").arg("0700")));
+        assert!(args.push(BwrapArg::new("--perms").arg("0700")));
+
+        let args = args.into_args().collect::<Vec<_>>();
+        let expected = [
+            "--bind",
+            "/tmp",
+            "/tmp",
+            "--unshare-all",
+            "--tmpfs",
+            "/home",
+            "--ro-bind",
+            "/a",
+            "/a",
+            "--share-net",
+            "--unshare-net",
+            "--perms",
+            "0700",
+            "--perms",
+            "0700",
+        ];
+        assert_eq!(args, expected);
+    }
+
+    #[test]
+    fn test_push_modifiers() {
+        let mut args = parse(&["--perms", "0700", "--dir", "/d"]);
+        args.append_to_end();
+
+        // Skipped with the option, nothing attached to the next one
+        assert!(args.push(BwrapArg::new("--perms").arg("0700")));
+        assert!(!args.push(BwrapArg::new("--dir").arg("/d")));
+        assert!(args.push(BwrapArg::new("--tmpfs").arg("/t")));
+        // Same option with other modifiers is not a duplicate
+        assert!(args.push(BwrapArg::new("--perms").arg("0755")));
+        assert!(args.push(BwrapArg::new("--dir").arg("/d")));
+
+        let args = args.into_args().collect::<Vec<_>>();
+        let expected = [
+            "--perms", "0700", "--dir", "/d", "--tmpfs", "/t", "--perms", "0755", "--dir", "/d",
+        ];
+        assert_eq!(args, expected);
+    }
+
+    #[test]
+    fn test_push_nested() {
+        let mut args = parse(&["--bind", "/a", "/h/x", "--tmpfs", "/h"]);
+        args.append_to_end();
+
+        // Earlier bind is hidden by tmpfs on parent
+        assert!(args.push(BwrapArg::new("--bind").arg("/a").arg("/h/x")));
+        assert!(!args.push(BwrapArg::new("--bind").arg("/a").arg("/h/x")));
+        assert!(args.push(BwrapArg::new("--tmpfs").arg("/h/x/y")));
+        assert!(args.push(BwrapArg::new("--bind").arg("/a").arg("/h/x")));
+    }
+}
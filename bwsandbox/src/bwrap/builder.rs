@@ -1,63 +1,105 @@
 use crate::{
-    bwrap::{ctl::BwrapCtl, proc::BwrapProc},
+    bwrap::{
+        args::{BwrapArg, BwrapArgs},
+        ctl::BwrapCtl,
+        proc::BwrapProc,
+    },
     error::AppError,
     services::{Context, ScopeCleanup, Service},
+    system::{CgroupConfig, Pty},
+};
+use std::{
+    ffi::OsString,
+    io::PipeWriter,
+    os::fd::{BorrowedFd, RawFd},
+    process::Command,
 };
-use std::{ffi::OsString, process::Command};
 
 #[derive(Debug)]
 pub struct ServiceCtx {
-    command: Command,
-    args: Vec<OsString>,
+    args: BwrapArgs,
     app: OsString,
+    cgroup: Option<CgroupConfig>,
+    inherit: Vec<RawFd>,
 }
 
 impl ServiceCtx {
-    fn new(app: OsString, args: Vec<OsString>) -> Self {
-        let command = Command::new(crate::utils::BWRAP_CMD);
-        Self { command, args, app }
+    fn new(app: OsString, args: BwrapArgs) -> Self {
+        Self {
+            args,
+            app,
+            cgroup: None,
+            inherit: Vec::new(),
+        }
     }
 
-    fn apply_args(&mut self) {
-        let args = std::mem::take(&mut self.args);
-        self.command.args(args);
+    fn into_command(self) -> (Command, OsString) {
+        let mut command = Command::new(crate::utils::BWRAP_CMD);
+        command.args(self.args.into_args());
+
+        // Inherit all output from bwrap and sandboxed app
+        command.stdout(std::process::Stdio::inherit());
+        command.stderr(std::process::Stdio::inherit());
+        command.stdin(std::process::Stdio::inherit());
+        (command, self.app)
     }
 }
 
 impl Context for ServiceCtx {
-    fn command_mut(&mut self) -> &mut std::process::Command {
-        &mut self.command
+    fn args(&self) -> &BwrapArgs {
+        &self.args
     }
 
-    fn arg_exist_before(&self, arg: &str) -> bool {
-        debug_assert!(!self.args.is_empty(), "Valid only for apply_before");
-        self.args.iter().any(|v| v == arg)
+    fn args_mut(&mut self) -> &mut BwrapArgs {
+        &mut self.args
     }
 
     fn bin(&self) -> &std::ffi::OsStr {
         &self.app
     }
+
+    fn set_cgroup(&mut self, cgroup: CgroupConfig) {
+        match &mut self.cgroup {
+            Some(current) => current.extend(cgroup),
+            None => self.cgroup = Some(cgroup),
+        }
+    }
+
+    fn inherit_fd(&mut self, fd: RawFd) {
+        self.inherit.push(fd);
+    }
 }
 
 #[derive(Debug)]
 pub struct ProcBuilder {
     ctx: ServiceCtx,
+    pty: bool,
+    output: Option<(PipeWriter, PipeWriter)>,
 }
 
 impl ProcBuilder {
-    pub fn new(app: OsString, args: Vec<OsString>) -> Self {
-        let mut ctx = ServiceCtx::new(app, args);
+    pub fn new(app: OsString, args: Vec<OsString>) -> Result<Self, AppError> {
+        let mut ctx = ServiceCtx::new(app, BwrapArgs::parse(args)?);
         // Allow access to services resources for sandboxed app (f.e. proxy dbus socket)
-        ctx.command_mut().arg("--bind");
-        ctx.command_mut().arg(crate::utils::temp_dir());
-        ctx.command_mut().arg(crate::utils::temp_dir());
+        let temp_dir = crate::utils::temp_dir();
+        ctx.args_mut()
+            .push(BwrapArg::new("--bind").arg(temp_dir).arg(temp_dir));
+
+        Ok(Self {
+            ctx,
+            pty: false,
+            output: None,
+        })
+    }
 
-        // Inherit all output from bwrap and sandboxed app
-        ctx.command_mut().stdout(std::process::Stdio::inherit());
-        ctx.command_mut().stderr(std::process::Stdio::inherit());
-        ctx.command_mut().stdin(std::process::Stdio::inherit());
+    // Sandbox gets new pseudoterminal instead of the user terminal
+    pub fn set_pty(&mut self, pty: bool) {
+        self.pty = pty;
+    }
 
-        Self { ctx }
+    // Stdout and stderr of bwrap and sandboxed app, ignored with pseudoterminal
+    pub fn set_output(&mut self, output: Option<(PipeWriter, PipeWriter)>) {
+        self.output = output;
     }
 
     pub fn apply_services<S>(&mut self, services: &mut [S]) -> Result<ScopeCleanup, AppError>
@@ -69,7 +111,8 @@ impl ProcBuilder {
             let scope = it.apply_before(&mut self.ctx)?;
             cleanup.push(scope);
         }
-        self.ctx.apply_args();
+        // Services args inserted before profile args so far
+        self.ctx.args_mut().append_to_end();
         for it in services.iter_mut() {
             let scope = it.apply_after(&mut self.ctx)?;
             cleanup.push(scope);
@@ -78,11 +121,44 @@ impl ProcBuilder {
         Ok(cleanup)
     }
 
-    pub fn spawn(self, app_args: Vec<OsString>) -> Result<BwrapProc, AppError> {
+    pub fn args(&self) -> &BwrapArgs {
+        self.ctx.args()
+    }
+
+    // Final command without lifecycle fds, nothing will be spawned
+    pub fn into_command(self, app_args: Vec<OsString>) -> Command {
+        let (mut command, app) = self.ctx.into_command();
+        command.arg(app).args(app_args);
+        command
+    }
+
+    pub fn spawn(mut self, app_args: Vec<OsString>) -> Result<BwrapProc, AppError> {
         use crate::system::{AsFdArg, SharedPipe};
 
-        let app = self.ctx.app;
-        let mut command = self.ctx.command;
+        let cgroup = self
+            .ctx
+            .cgroup
+            .take()
+            .map(CgroupConfig::create)
+            .transpose()?;
+        // bwrap setsid() would drop pseudoterminal as controlling terminal and job control with it,
+        // TIOCSTI reaches only the pseudoterminal anyway
+        if self.pty {
+            self.ctx.args_mut().remove("--new-session");
+        }
+        let inherit = std::mem::take(&mut self.ctx.inherit);
+        let (mut command, app) = self.ctx.into_command();
+        for fd in inherit {
+            // Services keep their fds open until they are started, that is after spawn
+            command.inherit_fd(&unsafe { BorrowedFd::borrow_raw(fd) })?;
+        }
+        let pty = self.pty.then(Pty::open).transpose()?;
+        if let Some(pty) = &pty {
+            pty.attach(&mut command)?;
+        } else if let Some((stdout, stderr)) = self.output.take() {
+            command.stdout(stdout);
+            command.stderr(stderr);
+        }
 
         // Setup ready block
         let mut block = SharedPipe::new()?;
@@ -101,7 +177,10 @@ impl ProcBuilder {
         let child = command
             .spawn()
             .map_err(AppError::spawn(crate::utils::BWRAP_CMD))?;
+        // Holds slave copies
+        drop(command);
+        let pty = pty.map(Pty::relay).transpose()?;
         let ctl = BwrapCtl::new(status.into_rx(), block.into_tx());
-        BwrapProc::new(child, ctl)
+        BwrapProc::new(child, ctl, cgroup, pty)
     }
 }
@@ -1,7 +1,6 @@
-use crate::bwrap::{SandboxStatus, events::EventsReader};
+use crate::bwrap::{SandboxExit, SandboxStatus, events::EventsReader, signals::SignalForwarder};
 use crate::error::AppError;
 use std::io::{PipeReader, PipeWriter};
-use std::process::ExitStatus;
 
 #[derive(Debug)]
 pub struct BwrapCtl {
@@ -28,26 +27,22 @@ impl BwrapCtl {
         self.status_rx.try_next()
     }
 
-    pub fn wait_exit(&mut self) -> Result<ExitStatus, AppError> {
-        use super::{events::Events, sigterm::SigTerm};
-        use linux_raw_sys::general::SIGINT;
-        use std::os::unix::process::ExitStatusExt;
+    pub fn wait_exit(&mut self, sig: &SignalForwarder) -> Result<SandboxExit, AppError> {
+        use super::events::Events;
 
-        let sig = SigTerm::register()?;
         loop {
             match self.status_rx.try_next::<Events>() {
                 Ok(Events::Exit(status)) => {
-                    return Ok(ExitStatus::from_raw(status.exit_code));
+                    return Ok(SandboxExit::from_code(status.exit_code));
                 }
                 Ok(evt) => {
                     log::warn!("Unknown bwrap event: {evt:?}");
                 }
-                Err(e) if sig.is_terminated() => {
-                    log::info!("SIGTERM/SIGINT received: {e:?}");
-                    let code = i32::try_from(SIGINT).expect("SIGINT u32 -> i32");
-                    return Ok(ExitStatus::from_raw(code));
-                }
                 Err(e) => {
+                    if let Some(signal) = sig.signal() {
+                        log::info!("Signal {signal} received: {e:?}");
+                        return Ok(SandboxExit::from_signal(signal));
+                    }
                     log::warn!("bwrap unexpected exit: {e:?}");
                     return Err(e);
                 }
@@ -1,5 +1,5 @@
 use crate::error::AppError;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::io::{BufRead, BufReader, ErrorKind::UnexpectedEof};
 
 pub trait EventType: serde::de::DeserializeOwned {}
@@ -15,7 +15,7 @@ pub enum Events {
 impl EventType for Events {}
 
 // Example: { "child-pid": 77360, "cgroup-namespace": 4026534046, "ipc-namespace": 4026534044, "mnt-namespace": 4026534042, "net-namespace": 4026534047, "pid-namespace": 4026534045, "uts-namespace": 4026534043 }
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
 #[serde(rename_all = "kebab-case")]
 #[allow(unused)] // Keep structure in sync with bwrap events
 pub struct SandboxStatus {
@@ -0,0 +1,121 @@
+use crate::{error::AppError, system::ResourceUsage};
+use serde::Serialize;
+use std::{path::Path, process::ExitCode};
+
+// Shell convention, bwrap reports signaled child the same way
+const SIGNAL_EXIT_BASE: i32 = 128;
+const SIGNAL_MAX: i32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SandboxExit {
+    pub exit_code: i32,
+    pub signal: Option<i32>,
+}
+
+impl SandboxExit {
+    // bwrap --json-status-fd reports WEXITSTATUS or 128 + WTERMSIG, plain `exit 130` looks the
+    // same there, so use it only when no real wait status exists
+    pub fn from_code(exit_code: i32) -> Self {
+        let signal = exit_code - SIGNAL_EXIT_BASE;
+        let signal = (1..=SIGNAL_MAX).contains(&signal).then_some(signal);
+        Self { exit_code, signal }
+    }
+
+    pub fn from_signal(signal: i32) -> Self {
+        Self {
+            exit_code: SIGNAL_EXIT_BASE + signal,
+            signal: Some(signal),
+        }
+    }
+
+    pub fn from_status(status: std::process::ExitStatus) -> Self {
+        use std::os::unix::process::ExitStatusExt;
+
+        match status.signal() {
+            Some(signal) => Self::from_signal(signal),
+            None => Self {
+                exit_code: status.code().unwrap_or_default(),
+                signal: None,
+            },
+        }
+    }
+
+    pub fn exit_code(&self) -> ExitCode {
+        let code = u8::try_from(self.exit_code).unwrap_or(u8::MAX);
+        ExitCode::from(code)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExitReport {
+    #[serde(flatten)]
+    pub exit: SandboxExit,
+    pub duration_ms: u64,
+    pub child_pid: u32,
+    pub usage: UsageReport,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct UsageReport {
+    // Whole sandbox tree, from cgroup if cgroup service is used
+    pub sandbox: ResourceUsage,
+    pub services: Vec<ServiceUsage>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServiceUsage {
+    pub name: String,
+    #[serde(flatten)]
+    pub usage: ResourceUsage,
+}
+
+impl UsageReport {
+    pub fn log(&self) {
+        log::info!("[usage] sandbox: {}", self.sandbox);
+        for it in &self.services {
+            log::info!("[usage] {}: {}", it.name, it.usage);
+        }
+    }
+}
+
+impl ExitReport {
+    pub fn write(&self, path: &Path) -> Result<(), AppError> {
+        let json = serde_json::to_vec_pretty(self).map_err(AppError::json("exit report"))?;
+        std::fs::write(path, json).map_err(AppError::file(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code() {
+        let exit = SandboxExit::from_code(0);
+        assert_eq!(exit.signal, None);
+        assert_eq!(exit.exit_code(), ExitCode::SUCCESS);
+
+        let exit = SandboxExit::from_code(3);
+        assert_eq!(exit.signal, None);
+        assert_eq!(exit.exit_code, 3);
+
+        // SIGKILL
+        let exit = SandboxExit::from_code(137);
+        assert_eq!(exit.signal, Some(9));
+
+        let exit = SandboxExit::from_signal(15);
+        assert_eq!(exit, SandboxExit::from_code(143));
+    }
+
+    #[test]
+    fn test_from_status() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let exit = SandboxExit::from_status(std::process::ExitStatus::from_raw(130 << 8));
+        assert_eq!(exit.exit_code, 130);
+        assert_eq!(exit.signal, None);
+
+        let exit = SandboxExit::from_status(std::process::ExitStatus::from_raw(2));
+        assert_eq!(exit, SandboxExit::from_signal(2));
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{
+    bwrap::{BwrapArg, BwrapArgs},
+    error::AppError,
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum Severity {
+    Off,
+    Warn,
+    Deny,
+}
+
+// Profile [bwrap.lint] section, `default` used for every rule not set explicitly
+#[derive(Debug, Deserialize)]
+pub struct LintConfig {
+    #[serde(default = "default_severity")]
+    default: Severity,
+    shadowed_mount: Option<Severity>,
+    duplicate_namespace: Option<Severity>,
+    share_net_with_net_service: Option<Severity>,
+    writable_sensitive_bind: Option<Severity>,
+    missing_new_session: Option<Severity>,
+}
+
+fn default_severity() -> Severity {
+    Severity::Warn
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            default: default_severity(),
+            shadowed_mount: None,
+            duplicate_namespace: None,
+            share_net_with_net_service: None,
+            writable_sensitive_bind: None,
+            missing_new_session: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Rule {
+    ShadowedMount,
+    DuplicateNamespace,
+    ShareNetWithNetService,
+    WritableSensitiveBind,
+    MissingNewSession,
+}
+
+impl Rule {
+    fn name(self) -> &'static str {
+        match self {
+            Self::ShadowedMount => "shadowed_mount",
+            Self::DuplicateNamespace => "duplicate_namespace",
+            Self::ShareNetWithNetService => "share_net_with_net_service",
+            Self::WritableSensitiveBind => "writable_sensitive_bind",
+            Self::MissingNewSession => "missing_new_session",
+        }
+    }
+}
+
+impl LintConfig {
+    fn severity(&self, rule: Rule) -> Severity {
+        let severity = match rule {
+            Rule::ShadowedMount => self.shadowed_mount,
+            Rule::DuplicateNamespace => self.duplicate_namespace,
+            Rule::ShareNetWithNetService => self.share_net_with_net_service,
+            Rule::WritableSensitiveBind => self.writable_sensitive_bind,
+            Rule::MissingNewSession => self.missing_new_session,
+        };
+        severity.unwrap_or(self.default)
+    }
+
+    // Every problem logged, launch rejected if any denied problem found
+    pub fn run(&self, args: &BwrapArgs, net_service: bool, pty: bool) -> Result<(), AppError> {
+        let mut denied = 0;
+        for (rule, message) in lint(args, net_service, pty) {
+            match self.severity(rule) {
+                Severity::Off => {}
+                Severity::Warn => log::warn!("[{}] {message}", rule.name()),
+                Severity::Deny => {
+                    log::error!("[{}] {message}", rule.name());
+                    denied += 1;
+                }
+            }
+        }
+
+        if denied > 0 {
+            return Err(AppError::Lint(denied));
+        }
+        Ok(())
+    }
+}
+
+fn lint(args: &BwrapArgs, net_service: bool, pty: bool) -> Vec<(Rule, String)> {
+    let mut found = Vec::new();
+    shadowed_mounts(args, &mut found);
+    duplicate_namespaces(args, &mut found);
+    if net_service && args.iter().any(|v| v.is("--share-net")) {
+        let message = "--share-net used together with pasta or slirp4netns service".to_owned();
+        found.push((Rule::ShareNetWithNetService, message));
+    }
+    writable_sensitive_binds(args, &sensitive_paths(), &mut found);
+    // Pseudoterminal of the sandbox is not the user terminal, nothing to inject into
+    if !pty && !args.iter().any(|v| v.is("--new-session")) {
+        let message = "--new-session is missing, sandboxed app can inject input into terminal";
+        found.push((Rule::MissingNewSession, message.to_owned()));
+    }
+    found
+}
+
+// Later mount at the same path or at any parent dir hides the earlier one
+fn shadowed_mounts(args: &BwrapArgs, found: &mut Vec<(Rule, String)>) {
+    let mounts = args
+        .iter()
+        .filter_map(|v| Some((v, v.mount_dest()?)))
+        .collect::<Vec<_>>();
+
+    for (idx, (arg, dest)) in mounts.iter().enumerate() {
+        let shadowed_by = mounts[idx + 1..]
+            .iter()
+            .find(|(_, later)| dest.starts_with(later));
+        if let Some((later, _)) = shadowed_by {
+            let message = format!("{} is shadowed by later {}", display(arg), display(later));
+            found.push((Rule::ShadowedMount, message));
+        }
+    }
+}
+
+fn duplicate_namespaces(args: &BwrapArgs, found: &mut Vec<(Rule, String)>) {
+    let flags = args
+        .iter()
+        .filter(|v| v.values().is_empty())
+        .filter(|v| v.name().to_str().is_some_and(is_namespace_flag))
+        .collect::<Vec<_>>();
+
+    let unshare_all = flags.iter().any(|v| v.is("--unshare-all"));
+    for (idx, arg) in flags.iter().enumerate() {
+        if flags[..idx].contains(arg) {
+            let message = format!("{} is set more than once", display(arg));
+            found.push((Rule::DuplicateNamespace, message));
+        } else if unshare_all && !arg.is("--unshare-all") && !arg.is("--share-net") {
+            let message = format!("{} is redundant with --unshare-all", display(arg));
+            found.push((Rule::DuplicateNamespace, message));
+        }
+    }
+}
+
+fn is_namespace_flag(name: &str) -> bool {
+    name.starts_with("--unshare-") || name == "--share-net"
+}
+
+// Host paths which must never be writable from sandbox
+// Path with flag whether anything inside is sensitive too, binds inside $HOME are ordinary
+fn sensitive_paths() -> Vec<(PathBuf, bool)> {
+    let mut paths = Vec::new();
+    if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        paths.push((home.join(".ssh"), true));
+        paths.push((home.join(".gnupg"), true));
+        paths.push((home, false));
+    }
+
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+    // unix:path=/run/user/1000/bus,guid=...
+    let session_bus = std::env::var("DBUS_SESSION_BUS_ADDRESS")
+        .ok()
+        .and_then(|v| {
+            let path = v.strip_prefix("unix:path=")?;
+            Some(PathBuf::from(path.split(',').next()?))
+        });
+    let session_bus = session_bus.or_else(|| runtime_dir.as_ref().map(|v| v.join("bus")));
+    paths.extend(session_bus.map(|v| (v, false)));
+    paths.extend(runtime_dir.map(|v| (v, false)));
+    paths
+}
+
+fn writable_sensitive_binds(
+    args: &BwrapArgs,
+    sensitive: &[(PathBuf, bool)],
+    found: &mut Vec<(Rule, String)>,
+) {
+    let binds = args.iter().filter(|v| {
+        let name = v.name().to_str();
+        matches!(
+            name,
+            Some("--bind" | "--bind-try" | "--dev-bind" | "--dev-bind-try")
+        )
+    });
+
+    for arg in binds {
+        let src = Path::new(&arg.values()[0]);
+        let src = src.canonicalize().unwrap_or_else(|_| src.into());
+        // Bind of any parent dir exposes sensitive path as well
+        let exposed = sensitive.iter().find(|(v, inside)| {
+            let v = v.canonicalize().unwrap_or_else(|_| PathBuf::clone(v));
+            v.starts_with(&src) || (*inside && src.starts_with(&v))
+        });
+        if let Some((path, _)) = exposed {
+            let message = format!("{} exposes {} as writable", display(arg), path.display());
+            found.push((Rule::WritableSensitiveBind, message));
+        }
+    }
+}
+
+fn display(arg: &BwrapArg) -> String {
+    let mut items = vec![arg.name().to_string_lossy()];
+    items.extend(arg.values().iter().map(|v| v.to_string_lossy()));
+    items.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn parse(args: &[&str]) -> BwrapArgs {
+        BwrapArgs::parse(args.iter().map(OsString::from).collect()).unwrap()
+    }
+
+    fn rules(found: &[(Rule, String)]) -> Vec<&'static str> {
+        found.iter().map(|(rule, _)| rule.name()).collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        let args = parse(&[
+            "--unshare-all",
+            "--unshare-net",
+            "--share-net",
+            "--share-net",
+            "--ro-bind",
+            "/home/user/.config",
+            "/home/user/.config",
+            "--tmpfs",
+            "/home",
+            "--new-session",
+        ]);
+        let found = lint(&args, true, false);
+        assert_eq!(
+            rules(&found),
+            [
+                "shadowed_mount",
+                "duplicate_namespace",
+                "duplicate_namespace",
+                "share_net_with_net_service"
+            ]
+        );
+
+        let args = parse(&["--bind", "/home", "/home", "--ro-bind", "/usr", "/usr"]);
+        let mut found = Vec::new();
+        let sensitive = [
+            (PathBuf::from("/home/user/.ssh"), true),
+            (PathBuf::from("/home/user"), false),
+        ];
+        writable_sensitive_binds(&args, &sensitive, &mut found);
+        assert_eq!(rules(&found), ["writable_sensitive_bind"]);
+
+        let keys = "/home/user/.ssh/authorized_keys";
+        let args = parse(&[
+            "--bind",
+            keys,
+            keys,
+            "--bind",
+            "/home/user/.sshd",
+            "/tmp",
+            "--bind",
+            "/home/user/Downloads",
+            "/tmp",
+        ]);
+        let mut found = Vec::new();
+        writable_sensitive_binds(&args, &sensitive, &mut found);
+        assert_eq!(rules(&found), ["writable_sensitive_bind"]);
+
+        let args = parse(&["--unshare-all", "--die-with-parent"]);
+        let found = lint(&args, false, false);
+        assert_eq!(rules(&found), ["missing_new_session"]);
+        assert!(lint(&args, false, true).is_empty());
+    }
+
+    #[test]
+    fn test_severity() {
+        let config: LintConfig = toml::from_str(
+            r#"
+            default = "deny"
+            missing_new_session = "off"
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.run(&parse(&["--unshare-all"]), false, false).is_ok());
+        assert!(config.run(&parse(&["--unshare-all"]), true, false).is_ok());
+        let args = parse(&["--tmpfs", "/home", "--tmpfs", "/home"]);
+        assert!(config.run(&args, false, false).is_err());
+    }
+}
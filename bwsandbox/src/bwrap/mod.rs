@@ -1,8 +1,16 @@
+mod args;
 mod builder;
 mod ctl;
 mod events;
+mod exit;
+mod lint;
 mod proc;
-mod sigterm;
+mod signals;
 
+pub use args::{BwrapArg, BwrapArgs};
 pub use builder::ProcBuilder;
 pub use events::SandboxStatus;
+pub use exit::{ExitReport, SandboxExit, ServiceUsage, UsageReport};
+pub use lint::LintConfig;
+pub use proc::{StopPolicy, app_pid, signal_app, terminate};
+pub use signals::FORWARDED_SIGNALS;
@@ -1,12 +1,15 @@
 use crate::{
-    bwrap::{SandboxStatus, ctl::BwrapCtl},
+    bwrap::{ExitReport, SandboxStatus, UsageReport, ctl::BwrapCtl, signals::SignalForwarder},
     error::AppError,
     services::BwrapInfo,
-    system::PidFd,
+    system::{Cgroup, PidFd, PtyRelay, ResourceUsage},
     utils,
 };
 use rustix::process::Signal;
-use std::process::{Child, ExitStatus};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Child;
+use std::time::{Duration, Instant};
 
 // Notes:
 // - bwrap treats --block-fd EOF as "green" flag to launch sandboxed app
@@ -19,7 +22,23 @@ use std::process::{Child, ExitStatus};
 // Mitigation:
 // - Collision chance is very low for such small amout of time
 // - App designed to work from unpriveledged user, so in the worst case scenario unpriveldged process got killed
-// - TODO: Use cgroups and kill whole cgroup, but keep in mind compatibility issues
+// - With cgroup service the whole sandbox tree is killed with cgroup.kill, no pids involved
+
+// How sandbox is stopped: SIGTERM, `signal` after `timeout`, SIGKILL after another `timeout`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct StopPolicy {
+    pub timeout: Duration,
+    pub signal: i32,
+}
+
+impl Default for StopPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: utils::SIGTERM_TIMEOUT,
+            signal: Signal::KILL.as_raw(),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct BwrapProc {
@@ -27,49 +46,147 @@ pub struct BwrapProc {
     ctl: BwrapCtl,
     status: SandboxStatus,
     child_pidfd: PidFd,
+    started: Instant,
+    cgroup: Option<Cgroup>,
+    stop: StopPolicy,
+    // Set once top-level bwrap is reaped
+    usage: Option<ResourceUsage>,
+    pty: Option<PtyRelay>,
 }
 
 impl BwrapProc {
-    pub fn new(proc: Child, mut ctl: BwrapCtl) -> Result<Self, AppError> {
+    pub fn new(
+        proc: Child,
+        mut ctl: BwrapCtl,
+        cgroup: Option<Cgroup>,
+        pty: Option<PtyRelay>,
+    ) -> Result<Self, AppError> {
         // FIXME: Potential race if child killed in between status and pifd_open
         let status = ctl.wait_status()?;
         let child_pidfd = PidFd::from_pid(status.child_pid)?;
 
-        Ok(Self {
+        let proc = Self {
             proc,
             ctl,
             status,
             child_pidfd,
-        })
+            started: Instant::now(),
+            cgroup,
+            stop: StopPolicy::default(),
+            usage: None,
+            pty,
+        };
+        // App is still blocked, so every process spawned later inherits cgroup
+        if let Some(cgroup) = &proc.cgroup {
+            cgroup.add_pid(proc.proc.id())?;
+            cgroup.add_pid(status.child_pid)?;
+        }
+        Ok(proc)
+    }
+
+    pub fn with_stop_policy(mut self, stop: StopPolicy) -> Self {
+        self.stop = stop;
+        self
     }
 
     pub fn bwrap_info(&self) -> BwrapInfo {
         BwrapInfo::new(self.proc.id(), self.status)
     }
 
-    pub fn wait(mut self) -> Result<ExitStatus, AppError> {
+    pub fn cgroup(&self) -> Option<&Path> {
+        self.cgroup.as_ref().map(Cgroup::path)
+    }
+
+    pub fn wait(mut self) -> Result<ExitReport, AppError> {
+        // Registered before unblock, so no signal is lost while app starts
+        let forwarder =
+            SignalForwarder::start(self.child_pidfd.try_clone()?, self.status.child_pid)?;
         self.ctl.unblock();
-        let status = self.ctl.wait_exit()?;
-        Ok(status)
+        let exit = self.ctl.wait_exit(&forwarder)?;
+        drop(forwarder);
+        let duration = self.started.elapsed().as_millis();
+        // Counters are final only after the whole sandbox is gone
+        let usage = self.kill()?;
+        // Output left in pseudoterminal goes before exit report
+        drop(self.pty.take());
+        Ok(ExitReport {
+            exit,
+            duration_ms: u64::try_from(duration).unwrap_or(u64::MAX),
+            child_pid: self.status.child_pid,
+            usage: UsageReport {
+                sandbox: usage,
+                services: Vec::new(),
+            },
+        })
     }
 
-    fn kill(&mut self) -> Result<(), AppError> {
-        // Try to stop child process gracefully
-        let _ = self.child_pidfd.send_sig(Signal::TERM);
-        let status = self.child_pidfd.wait(utils::SIGTERM_TIMEOUT);
-        if status.is_err() {
-            log::error!("Failed to stop bwrap gracefuly");
-            let status = self.child_pidfd.send_sig(Signal::KILL);
-            log::error!("SIGKILL status: {status:?}");
+    fn kill(&mut self) -> Result<ResourceUsage, AppError> {
+        if let Some(usage) = self.usage {
+            return Ok(usage);
         }
 
+        let cgroup = self.cgroup.as_ref().map(Cgroup::path);
+        terminate(&self.child_pidfd, self.status.child_pid, cgroup, self.stop);
+
         // Now safe to wait until top-level bwrap finish
-        self.proc
-            .wait()
-            .map_err(AppError::io("bwrap unknown state"))?;
+        // bwrap waits for nested processes, so rusage covers the whole tree except escaped ones
+        let mut usage = ResourceUsage::wait(self.proc.id())?;
 
-        Ok(())
+        // Processes which left sandbox process tree (f.e. daemonized wine) are still in cgroup
+        if let Some(cgroup) = &self.cgroup {
+            cgroup.kill()?;
+            usage = cgroup.usage(usage);
+        }
+
+        self.usage = Some(usage);
+        Ok(usage)
+    }
+}
+
+// With --unshare-pid bwrap stays as pid 1 of the sandbox and app is its child
+pub fn app_pid(child_pid: u32) -> u32 {
+    let read = |path: String| std::fs::read_to_string(path).unwrap_or_default();
+    if read(format!("/proc/{child_pid}/comm")).trim_end() != "bwrap" {
+        return child_pid;
+    }
+    let children = read(format!("/proc/{child_pid}/task/{child_pid}/children"));
+    let first = children.split_whitespace().next();
+    first.and_then(|v| v.parse().ok()).unwrap_or(child_pid)
+}
+
+// bwrap init with --unshare-pid has no handlers, app is resolved on every call
+// since it may not exist yet or be gone already
+pub fn signal_app(child: &PidFd, child_pid: u32, signal: Signal) -> Result<(), AppError> {
+    let pid = app_pid(child_pid);
+    let app = (pid != child_pid)
+        .then(|| PidFd::from_pid(pid).ok())
+        .flatten();
+    Ok(app.as_ref().unwrap_or(child).send_sig(signal)?)
+}
+
+// Try to stop child process gracefully, shared with `stop` command for instances of other processes
+// Graceful signals go to the app, bwrap exits with it; SIGKILL goes to bwrap and its whole tree
+pub fn terminate(child: &PidFd, child_pid: u32, cgroup: Option<&Path>, stop: StopPolicy) {
+    let _ = signal_app(child, child_pid, Signal::TERM);
+    if child.wait(stop.timeout).is_ok() {
+        return;
     }
+    log::error!("Failed to stop bwrap gracefuly");
+
+    let signal = Signal::from_named_raw(stop.signal).unwrap_or(Signal::KILL);
+    if signal != Signal::KILL {
+        let status = signal_app(child, child_pid, signal);
+        log::error!("Signal {} status: {status:?}", stop.signal);
+        if child.wait(stop.timeout).is_ok() {
+            return;
+        }
+    }
+
+    let status = match cgroup {
+        Some(cgroup) => crate::system::cgroup::kill(cgroup),
+        None => child.send_sig(Signal::KILL).map_err(Into::into),
+    };
+    log::error!("SIGKILL status: {status:?}");
 }
 
 impl Drop for BwrapProc {
@@ -0,0 +1,65 @@
+use crate::{bwrap::signal_app, error::AppError, system::PidFd};
+use rustix::process::Signal;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM, SIGUSR1, SIGUSR2, SIGWINCH};
+use signal_hook::iterator::{Handle, Signals};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI32, Ordering::SeqCst};
+use std::thread::JoinHandle;
+
+pub const FORWARDED_SIGNALS: [i32; 6] = [SIGTERM, SIGINT, SIGHUP, SIGUSR1, SIGUSR2, SIGWINCH];
+// Reported as sandbox exit reason if bwrap dies without exit status
+const TERMINATING: [i32; 3] = [SIGTERM, SIGINT, SIGHUP];
+
+// Signals sent to bwsandbox are passed to sandboxed child, so it can shut down gracefully,
+// reload config or resize terminal. bwrap with --new-session never gets them otherwise.
+#[derive(Debug)]
+pub struct SignalForwarder {
+    handle: Handle,
+    thread: Option<JoinHandle<()>>,
+    // Last terminating signal, 0 if nothing received yet
+    signal: Arc<AtomicI32>,
+}
+
+impl SignalForwarder {
+    pub fn start(child: PidFd, child_pid: u32) -> Result<Self, AppError> {
+        let mut signals = Signals::new(FORWARDED_SIGNALS).map_err(AppError::CtrlC)?;
+        let handle = signals.handle();
+        let signal = Arc::new(AtomicI32::new(0));
+
+        let last = signal.clone();
+        let thread = std::thread::spawn(move || {
+            for sig in signals.forever() {
+                if TERMINATING.contains(&sig) {
+                    last.store(sig, SeqCst);
+                }
+                let Some(forward) = Signal::from_named_raw(sig) else {
+                    continue;
+                };
+                log::info!("Forward signal {sig} to sandbox");
+                if let Err(e) = signal_app(&child, child_pid, forward) {
+                    log::warn!("Failed to forward signal {sig}: {e:?}");
+                }
+            }
+        });
+
+        Ok(Self {
+            handle,
+            thread: Some(thread),
+            signal,
+        })
+    }
+
+    pub fn signal(&self) -> Option<i32> {
+        let signal = self.signal.load(SeqCst);
+        (signal != 0).then_some(signal)
+    }
+}
+
+impl Drop for SignalForwarder {
+    fn drop(&mut self) {
+        self.handle.close();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use crate::bwrap::LintConfig;
+use crate::config::{Cmd, Mount};
+use crate::error::AppError;
+use serde::Deserialize;
+use std::ffi::OsString;
+
+// Profile [bwrap] section, typed mounts applied after inline and template args
+#[derive(Debug, Deserialize)]
+pub struct BwrapCmd {
+    #[serde(flatten)]
+    cmd: Cmd,
+    #[serde(default)]
+    mounts: Vec<Mount>,
+    #[serde(default)]
+    pub lint: LintConfig,
+}
+
+impl BwrapCmd {
+    pub fn collect_args(&self) -> Result<Vec<OsString>, AppError> {
+        let mut items = self.cmd.collect_args()?;
+        for (idx, it) in self.mounts.iter().enumerate() {
+            items.extend(it.to_args(idx)?);
+        }
+        Ok(items)
+    }
+
+    // Content of `file` mounts, args point to them since collect_args()
+    pub fn write_files(&self) -> Result<(), AppError> {
+        for (idx, it) in self.mounts.iter().enumerate() {
+            it.write_file(idx)?;
+        }
+        Ok(())
+    }
+
+    // Reports every broken mount, nothing written to workdir
+    pub fn check(&self) -> Vec<AppError> {
+        let cmd = self.cmd.collect_args().err();
+        let mounts = self.mounts.iter().filter_map(|v| v.validate().err());
+        cmd.into_iter().chain(mounts).collect()
+    }
+}
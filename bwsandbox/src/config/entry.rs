@@ -1,6 +1,9 @@
 use crate::{config::EnvVal, error::AppError};
-use serde::{Deserialize, de::DeserializeOwned};
-use std::path::PathBuf;
+use serde::{
+    Deserialize, Deserializer,
+    de::{DeserializeOwned, MapAccess, SeqAccess, Visitor, value},
+};
+use std::{marker::PhantomData, path::PathBuf};
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -27,3 +30,123 @@ impl<T: DeserializeOwned> Entry<T> {
         Ok(item)
     }
 }
+
+// Service start and apply order, names are sections or `section.instance`
+#[derive(Debug, Default, Deserialize)]
+pub struct Order {
+    #[serde(default)]
+    pub after: Vec<String>,
+    #[serde(default)]
+    pub before: Vec<String>,
+}
+
+// Order is kept in profile next to the entry, also for includes
+#[derive(Debug, Deserialize)]
+pub struct Ordered<T> {
+    #[serde(flatten)]
+    pub order: Order,
+    #[serde(flatten)]
+    pub entry: Entry<T>,
+}
+
+impl<T: DeserializeOwned> Ordered<T> {
+    pub fn load(self) -> Result<(Order, T), AppError> {
+        let config = self.entry.load(crate::utils::deserialize)?;
+        Ok((self.order, config))
+    }
+}
+
+// Table or array of tables, every table is a separate service instance
+#[derive(Debug)]
+pub struct Entries<T>(Vec<Ordered<T>>);
+
+impl<T> IntoIterator for Entries<T> {
+    type Item = Ordered<T>;
+    type IntoIter = std::vec::IntoIter<Ordered<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+// Not untagged enum, it would hide errors of the inner config
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Entries<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntriesVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for EntriesVisitor<T> {
+            type Value = Entries<T>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("table or array of tables")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                let entry = Ordered::deserialize(value::MapAccessDeserializer::new(map))?;
+                Ok(Entries(vec![entry]))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                let entries = Vec::deserialize(value::SeqAccessDeserializer::new(seq))?;
+                Ok(Entries(entries))
+            }
+        }
+
+        deserializer.deserialize_any(EntriesVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Item {
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Section {
+        item: Entries<Item>,
+    }
+
+    fn names(config: &str) -> Result<Vec<String>, toml::de::Error> {
+        let section: Section = toml::from_str(config)?;
+        let names = section.item.into_iter().map(|v| match v.entry {
+            Entry::Inline(v) => v.name,
+            Entry::Include { .. } => unreachable!(),
+        });
+        Ok(names.collect())
+    }
+
+    #[test]
+    fn test_entries() {
+        let one = names("item = { type = \"inline\", name = \"a\" }").unwrap();
+        assert_eq!(one, ["a"]);
+
+        let many = names(
+            r#"
+            [[item]]
+            type = "inline"
+            name = "a"
+
+            [[item]]
+            type = "inline"
+            name = "b"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(many, ["a", "b"]);
+
+        let ordered: Section =
+            toml::from_str("item = { type = \"inline\", name = \"a\", after = [\"b\"] }").unwrap();
+        let (order, item) = ordered.item.into_iter().next().unwrap().load().unwrap();
+        assert_eq!(order.after, ["b"]);
+        assert_eq!(item.name, "a");
+
+        // Inner errors are kept
+        let e = names("item = [{ type = \"inline\" }]").unwrap_err();
+        assert!(e.message().contains("missing field `name`"), "{e}");
+        assert!(names("item = 1").is_err());
+    }
+}
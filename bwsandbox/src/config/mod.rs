@@ -1,9 +1,13 @@
+mod bwrap;
 mod cmd;
 mod entry;
+mod mount;
 mod template;
 mod values;
 
+pub use bwrap::BwrapCmd;
 pub use cmd::Cmd;
-pub use entry::Entry;
+pub use entry::{Entries, Order, Ordered};
+pub use mount::Mount;
 pub use template::Template;
 pub use values::*;
@@ -0,0 +1,339 @@
+use crate::{config::EnvVal, error::AppError, utils};
+use serde::Deserialize;
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+const MAX_MODE: u32 = 0o7777;
+
+#[derive(Debug, Deserialize)]
+pub struct Mount {
+    #[serde(flatten)]
+    kind: MountKind,
+    // bwrap `--*-bind-try`, missing source ignored by bwrap
+    #[serde(default, rename = "try")]
+    try_missing: bool,
+    // Entry skipped on composition if any source is missing
+    #[serde(default)]
+    if_exists: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(tag = "type")]
+enum MountKind {
+    RoBind {
+        src: EnvVal<PathBuf>,
+        dest: Option<EnvVal<PathBuf>>,
+    },
+    Bind {
+        src: EnvVal<PathBuf>,
+        dest: Option<EnvVal<PathBuf>>,
+    },
+    DevBind {
+        src: EnvVal<PathBuf>,
+        dest: Option<EnvVal<PathBuf>>,
+    },
+    Tmpfs {
+        dest: EnvVal<PathBuf>,
+        size: Option<u64>,
+        mode: Option<u32>,
+    },
+    Symlink {
+        src: EnvVal<PathBuf>,
+        dest: EnvVal<PathBuf>,
+    },
+    // Read only without `rw_src` and `work_dir`
+    Overlay {
+        sources: Vec<EnvVal<PathBuf>>,
+        dest: EnvVal<PathBuf>,
+        rw_src: Option<EnvVal<PathBuf>>,
+        work_dir: Option<EnvVal<PathBuf>>,
+    },
+    Dir {
+        dest: EnvVal<PathBuf>,
+        mode: Option<u32>,
+    },
+    // Content written to workdir file and mounted read only
+    File {
+        dest: EnvVal<PathBuf>,
+        content: String,
+    },
+}
+
+impl Mount {
+    fn dest(&self) -> &Path {
+        match &self.kind {
+            MountKind::RoBind { src, dest }
+            | MountKind::Bind { src, dest }
+            | MountKind::DevBind { src, dest } => dest.as_ref().unwrap_or(src).as_inner(),
+            MountKind::Tmpfs { dest, .. }
+            | MountKind::Symlink { dest, .. }
+            | MountKind::Overlay { dest, .. }
+            | MountKind::Dir { dest, .. }
+            | MountKind::File { dest, .. } => dest.as_inner(),
+        }
+    }
+
+    // Host paths which must exist, symlink target is resolved inside sandbox
+    fn sources(&self) -> Vec<&Path> {
+        match &self.kind {
+            MountKind::RoBind { src, .. }
+            | MountKind::Bind { src, .. }
+            | MountKind::DevBind { src, .. } => vec![src.as_inner()],
+            MountKind::Overlay {
+                sources,
+                rw_src,
+                work_dir,
+                ..
+            } => sources
+                .iter()
+                .chain(rw_src)
+                .chain(work_dir)
+                .map(|v| v.as_inner().as_path())
+                .collect(),
+            MountKind::Tmpfs { .. }
+            | MountKind::Symlink { .. }
+            | MountKind::Dir { .. }
+            | MountKind::File { .. } => Vec::new(),
+        }
+    }
+
+    fn mode(&self) -> Option<u32> {
+        match &self.kind {
+            MountKind::Tmpfs { mode, .. } | MountKind::Dir { mode, .. } => *mode,
+            _ => None,
+        }
+    }
+
+    fn is_bind(&self) -> bool {
+        matches!(
+            self.kind,
+            MountKind::RoBind { .. } | MountKind::Bind { .. } | MountKind::DevBind { .. }
+        )
+    }
+
+    fn is_skipped(&self) -> bool {
+        self.if_exists && self.sources().iter().any(|v| !v.exists())
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        let dest = self.dest();
+        let err = |reason| Err(AppError::Mount(dest.into(), reason));
+
+        if !dest.is_absolute() {
+            return err("destination must be absolute");
+        }
+        if self.try_missing && !self.is_bind() {
+            return err("`try` supported only by bind mounts");
+        }
+        if self.if_exists && self.sources().is_empty() {
+            return err("`if_exists` supported only by mounts with sources");
+        }
+        if self.mode().is_some_and(|v| v > MAX_MODE) {
+            return err("mode must be in 0o0000..=0o7777 range");
+        }
+
+        match &self.kind {
+            MountKind::Symlink { src, .. } if src.as_inner().as_os_str().is_empty() => {
+                return err("symlink target is empty");
+            }
+            MountKind::Overlay { sources, .. } if sources.is_empty() => {
+                return err("overlay requires at least one source");
+            }
+            MountKind::Overlay {
+                rw_src, work_dir, ..
+            } if rw_src.is_some() != work_dir.is_some() => {
+                return err("overlay `rw_src` and `work_dir` must be set together");
+            }
+            _ => {}
+        }
+
+        if self.try_missing || self.if_exists {
+            return Ok(());
+        }
+        for it in self.sources() {
+            if !it.exists() {
+                return Err(AppError::file(it)(std::io::ErrorKind::NotFound.into()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_args(&self, idx: usize) -> Result<Vec<OsString>, AppError> {
+        self.validate()?;
+        if self.is_skipped() {
+            log::debug!(
+                "Mount skipped, source is missing: {}",
+                self.dest().display()
+            );
+            return Ok(Vec::new());
+        }
+
+        let src = |v: &EnvVal<PathBuf>| absolute(v.as_inner()).into_os_string();
+        let dest = OsString::from(self.dest());
+        let perms = self
+            .mode()
+            .map(|v| ["--perms".into(), format!("{v:04o}").into()]);
+
+        let mut args: Vec<OsString> = perms.into_iter().flatten().collect();
+        match &self.kind {
+            MountKind::RoBind { src: it, .. }
+            | MountKind::Bind { src: it, .. }
+            | MountKind::DevBind { src: it, .. } => {
+                args.extend([self.bind_flag().into(), src(it), dest]);
+            }
+            MountKind::Tmpfs { size, .. } => {
+                if let Some(size) = size {
+                    args.extend(["--size".into(), size.to_string().into()]);
+                }
+                args.extend(["--tmpfs".into(), dest]);
+            }
+            MountKind::Symlink { src: target, .. } => {
+                // Target is resolved inside sandbox, keep it as is
+                args.extend(["--symlink".into(), target.as_inner().into(), dest]);
+            }
+            MountKind::Overlay {
+                sources,
+                rw_src,
+                work_dir,
+                ..
+            } => {
+                for it in sources {
+                    args.extend(["--overlay-src".into(), src(it)]);
+                }
+                match (rw_src, work_dir) {
+                    (Some(rw_src), Some(work_dir)) => {
+                        args.extend(["--overlay".into(), src(rw_src), src(work_dir), dest]);
+                    }
+                    _ => args.extend(["--ro-overlay".into(), dest]),
+                }
+            }
+            MountKind::Dir { .. } => args.extend(["--dir".into(), dest]),
+            MountKind::File { .. } => {
+                args.extend(["--ro-bind".into(), file_path(idx).into(), dest]);
+            }
+        }
+
+        Ok(args)
+    }
+
+    // Written only right before spawn, dry run must not touch workdir
+    pub fn write_file(&self, idx: usize) -> Result<(), AppError> {
+        let MountKind::File { content, .. } = &self.kind else {
+            return Ok(());
+        };
+        let path = file_path(idx);
+        log::trace!("Create {}", path.display());
+        std::fs::write(&path, content).map_err(AppError::file(&path))
+    }
+
+    fn bind_flag(&self) -> &'static str {
+        match (&self.kind, self.try_missing) {
+            (MountKind::RoBind { .. }, false) => "--ro-bind",
+            (MountKind::RoBind { .. }, true) => "--ro-bind-try",
+            (MountKind::Bind { .. }, false) => "--bind",
+            (MountKind::Bind { .. }, true) => "--bind-try",
+            (MountKind::DevBind { .. }, false) => "--dev-bind",
+            (MountKind::DevBind { .. }, true) => "--dev-bind-try",
+            _ => unreachable!("Not a bind mount"),
+        }
+    }
+}
+
+fn file_path(idx: usize) -> PathBuf {
+    utils::temp_dir().join(format!("mount-file-{idx}"))
+}
+
+// Relative sources are relative to profile dir, same as template dir
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Inline table, same as profile `mounts = [{ ... }]` entry
+    fn parse(value: &str) -> Result<Mount, toml::de::Error> {
+        let mut table: toml::Table = toml::from_str(&format!("value = {value}"))?;
+        table.remove("value").unwrap().try_into()
+    }
+
+    #[test]
+    fn test_mount_args() {
+        let cases = [
+            (
+                r#"{ type = "ro-bind", src = "/usr" }"#,
+                vec!["--ro-bind", "/usr", "/usr"],
+            ),
+            (
+                r#"{ type = "bind", src = "/no/such/dir", dest = "/data", try = true }"#,
+                vec!["--bind-try", "/no/such/dir", "/data"],
+            ),
+            (
+                r#"{ type = "dev-bind", src = "/no/such/dir", if_exists = true }"#,
+                vec![],
+            ),
+            (
+                r#"{ type = "tmpfs", dest = "/home", size = 1024, mode = 0o700 }"#,
+                vec!["--perms", "0700", "--size", "1024", "--tmpfs", "/home"],
+            ),
+            (
+                r#"{ type = "symlink", src = "usr/lib", dest = "/lib" }"#,
+                vec!["--symlink", "usr/lib", "/lib"],
+            ),
+            (
+                r#"{ type = "overlay", sources = ["/usr", "/etc"], dest = "/mnt" }"#,
+                vec![
+                    "--overlay-src",
+                    "/usr",
+                    "--overlay-src",
+                    "/etc",
+                    "--ro-overlay",
+                    "/mnt",
+                ],
+            ),
+            (
+                r#"{ type = "dir", dest = "/run/user" }"#,
+                vec!["--dir", "/run/user"],
+            ),
+        ];
+
+        for (value, expected) in cases {
+            let args = parse(value).unwrap().to_args(0).unwrap();
+            assert_eq!(args, expected);
+        }
+    }
+
+    #[test]
+    fn test_mount_file() {
+        let mount = parse(r#"{ type = "file", dest = "/etc/motd", content = "hello" }"#).unwrap();
+        let args = mount.to_args(7).unwrap();
+        let path = file_path(7);
+        let expected: [OsString; 3] = ["--ro-bind".into(), path.clone().into(), "/etc/motd".into()];
+        assert_eq!(args, expected);
+        // Args only, content is written on spawn
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_mount_validate() {
+        let cases = [
+            r#"{ type = "ro-bind", src = "/no/such/dir" }"#,
+            r#"{ type = "ro-bind", src = "/usr", dest = "relative" }"#,
+            r#"{ type = "tmpfs", dest = "/tmp", try = true }"#,
+            r#"{ type = "dir", dest = "/tmp", if_exists = true }"#,
+            r#"{ type = "dir", dest = "/tmp", mode = 0o17777 }"#,
+            r#"{ type = "overlay", sources = [], dest = "/mnt" }"#,
+            r#"{ type = "overlay", sources = ["/usr"], dest = "/mnt", rw_src = "/tmp" }"#,
+        ];
+
+        for value in cases {
+            assert!(parse(value).unwrap().validate().is_err());
+        }
+
+        assert!(parse(r#"{ type = "no-such-type", dest = "/tmp" }"#).is_err());
+    }
+}
@@ -11,6 +11,13 @@ impl TempFileVal {
         Self(temp_dir().join(context))
     }
 
+    // Every instance of a service gets own file, f.e. `dbus-proxy-system`
+    pub fn for_instance(self, instance: &str) -> Self {
+        let mut name = self.0.into_os_string();
+        name.push(format!("-{instance}"));
+        Self(name.into())
+    }
+
     pub fn as_inner(&self) -> &Path {
         &self.0
     }
@@ -42,5 +49,8 @@ mod tests {
 
         let first2 = TempFileVal::new("first");
         assert_eq!(first, first2);
+
+        let instance = first2.for_instance("system");
+        assert_eq!(instance, TempFileVal::new("first-system"));
     }
 }
@@ -1,5 +1,6 @@
 use std::{
     borrow::Cow,
+    ffi::OsString,
     os::fd::RawFd,
     path::{Path, PathBuf},
 };
@@ -32,6 +33,46 @@ pub enum AppError {
     Spawn(String, std::io::Error),
     #[error("Unexpected or missing arguments")]
     BadArgs,
+    #[error("Missing [{0}] section")]
+    MissingSection(&'static str),
+    #[error("Unknown [{0}] section")]
+    UnknownSection(String),
+    #[error("Profile extends itself: {0:?}")]
+    ProfileCycle(PathBuf),
+    #[error("Profile {0:?} not found in {1:?}")]
+    ProfileNotFound(OsString, Vec<PathBuf>),
+    #[error("Missing value for bwrap option {0}")]
+    BwrapArgs(String),
+    #[error("Policy file {0:?} must be owned by root:root and not writable by others")]
+    PolicyPermissions(PathBuf),
+    #[error("Invalid policy: {0}")]
+    PolicyInvalid(String),
+    #[error("Profile breaks host policy with {0} violation(s)")]
+    Policy(usize),
+    #[error("Lint rejected bwrap args with {0} problem(s)")]
+    Lint(usize),
+    #[error("Mount {0:?}: {1}")]
+    Mount(PathBuf, &'static str),
+    #[error("Sandbox instance {0:?} is not running")]
+    InstanceNotFound(String),
+    #[error("Cgroup: {0}")]
+    Cgroup(String),
+    #[error("Lifecycle: {0}")]
+    Lifecycle(String),
+    #[error("Output: {0}")]
+    Output(String),
+    #[error("Hook: {0}")]
+    Hook(String),
+    #[error("Helper process: {0}")]
+    Process(String),
+    #[error("Service instance: {0}")]
+    Instance(String),
+    #[error("Service order: {0}")]
+    ServiceOrder(String),
+    #[error("D-Bus policy: {0}")]
+    DbusPolicy(String),
+    #[error("Invalid override {0}")]
+    Override(String),
     #[error(transparent)]
     ArgParser(#[from] lexopt::Error),
     #[error("Failed ffi call to libseccomp {0:?}")]
@@ -42,6 +83,10 @@ pub enum AppError {
     PipeAlloc(std::io::Error),
     #[error("Failed to parse bwrap event {0:?}")]
     BwrapEvent(serde_json::Error),
+    #[error("JSON {0}: {1:?}")]
+    Json(&'static str, serde_json::Error),
+    #[error("Failed to quote shell args: {0}")]
+    Quote(#[from] shlex::QuoteError),
     #[error("System call failed with {0:?}")]
     System(#[from] crate::system::Error),
     #[error("wl connect: {0:?}")]
@@ -94,6 +139,10 @@ impl AppError {
         move |e| Self::Io(Cow::Borrowed(src), e)
     }
 
+    pub fn json(src: &'static str) -> impl Fn(serde_json::Error) -> Self {
+        move |e| Self::Json(src, e)
+    }
+
     pub fn utf8<T: Into<Utf8Error>>(src: &'static str) -> impl Fn(T) -> Self {
         move |e| Self::Utf8(src, e.into())
     }
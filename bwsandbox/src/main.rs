@@ -1,4 +1,4 @@
-use std::process::{ExitCode, ExitStatus};
+use std::process::ExitCode;
 
 mod app;
 mod bwrap;
@@ -15,7 +15,7 @@ mod tests;
 
 fn main() -> ExitCode {
     setup_log();
-    let args = match app::Args::from_iter(std::env::args_os()) {
+    let cli = match app::Cli::from_iter(std::env::args_os()) {
         Ok(v) => v,
         Err(e) => {
             print_error(&e);
@@ -23,12 +23,20 @@ fn main() -> ExitCode {
         }
     };
 
-    if let Err(e) = run(args) {
-        print_error(&e);
-        return ExitCode::FAILURE;
-    }
+    let status = match cli {
+        app::Cli::Run(args) => run(args),
+        app::Cli::Check(args) => check(&args),
+        app::Cli::ListProfiles => app::App::list_profiles().map(|()| ExitCode::SUCCESS),
+        app::Cli::Ps => app::App::ps().map(|()| ExitCode::SUCCESS),
+        app::Cli::Stop(id) => app::App::stop(&id).map(|()| ExitCode::SUCCESS),
+        app::Cli::Enter(id, command) => app::App::enter(&id, &command),
+        app::Cli::Freeze(id, frozen) => app::App::freeze(&id, frozen).map(|()| ExitCode::SUCCESS),
+    };
 
-    ExitCode::SUCCESS
+    status.unwrap_or_else(|e| {
+        print_error(&e);
+        ExitCode::FAILURE
+    })
 }
 
 fn setup_log() {
@@ -44,12 +52,20 @@ fn setup_log() {
         .init();
 }
 
-fn run(args: app::Args) -> Result<ExitStatus, error::AppError> {
+fn run(args: app::Args) -> Result<ExitCode, error::AppError> {
     let _guard = temp_dir::TempDirGuard::new(utils::temp_dir())?;
     let status = app::App::start(args)?;
     Ok(status)
 }
 
+fn check(args: &app::CheckArgs) -> Result<ExitCode, error::AppError> {
+    if app::App::check(args)? {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
+
 fn print_error(e: &error::AppError) {
     log::info!("{e:#?}");
     log::error!("{e}");
@@ -58,10 +74,36 @@ fn print_error(e: &error::AppError) {
 fn print_help() -> ExitCode {
     println!("-----------------");
     println!("Usage: {} [--flags] -- app --arg1 arg2", utils::APP_NAME);
+    println!(
+        "       {} check <-f profile.toml | -n name>",
+        utils::APP_NAME
+    );
+    println!("       {} list-profiles", utils::APP_NAME);
+    println!("       {} ps", utils::APP_NAME);
+    println!(
+        "       {} <stop | freeze | thaw> <sandbox id>",
+        utils::APP_NAME
+    );
+    println!(
+        "       {} enter <sandbox id> [--] command --arg1 arg2",
+        utils::APP_NAME
+    );
     println!("\t-f, --config-file  <path to profile.toml>");
-    println!("\t-n, --config-name  <profile name in $XDG_CONFIG_PATH/bwsandbox>");
+    println!("\t-n, --config-name  <profile name in profiles search path>");
     println!("\t-a, --config-auto");
-    println!("\t\tWill use <app> as profile name in $XDG_CONFIG_PATH/bwsandbox");
+    println!("\t\tWill select profile by auto.toml rules or use <app> basename as profile name");
+    println!("\t--dry-run[=shell|json]");
+    println!("\t\tPrint composed bwrap and services commands without launching");
+    println!("\t--set <key=value>");
+    println!("\t\tOverride bwrap template context value, env variables are expanded");
+    println!("\t--set-service <service.field=value>");
+    println!("\t\tOverride service config field, value parsed as toml or used as string");
+    println!("\t--pty");
+    println!("\t\tRun app in new pseudoterminal, relay I/O and window size from the user terminal");
+    println!("\t--exit-report <path>");
+    println!(
+        "\t\tWrite JSON with exit code, signal, duration, child pid and resource usage on exit"
+    );
     println!("-----------------");
     ExitCode::SUCCESS
 }
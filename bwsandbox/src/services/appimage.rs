@@ -1,11 +1,19 @@
 use crate::{
+    bwrap::BwrapArg,
     error::AppError,
     services::{BwrapInfo, Context, HandleType, Scope, Service},
 };
 use serde::Deserialize;
+use std::process::Command;
+
+const NAME: &str = "appimage extract";
 
 #[derive(Debug, Deserialize)]
 pub struct AppImageExtract {
+    // Needed for more than one entry, added to service name
+    name: Option<String>,
+    #[serde(skip)]
+    service_name: String,
     #[serde(default = "extract_and_run_default")]
     extract_and_run: bool,
 }
@@ -15,16 +23,28 @@ fn extract_and_run_default() -> bool {
 }
 
 impl AppImageExtract {
-    // Keep consistent across services
-    #[allow(clippy::unnecessary_wraps)]
-    pub fn from_config(config: Self) -> Result<Self, AppError> {
+    pub fn from_config(mut config: Self) -> Result<Self, AppError> {
+        config.service_name = crate::services::instance_name(NAME, config.instance())?;
         Ok(config)
     }
+
+    // Keep interface consistent
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn check_config(config: Self) -> Vec<AppError> {
+        crate::services::instance_name(NAME, config.instance())
+            .err()
+            .into_iter()
+            .collect()
+    }
+
+    pub fn instance(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
 }
 
 impl<C: Context> Service<C> for AppImageExtract {
-    fn name(&self) -> &'static str {
-        "appimage extract"
+    fn name(&self) -> &str {
+        &self.service_name
     }
 
     fn apply_before(&mut self, _: &mut C) -> Result<Scope, AppError> {
@@ -35,10 +55,10 @@ impl<C: Context> Service<C> for AppImageExtract {
         // Should work for appimage v2
         // https://github.com/AppImage/AppImageKit/issues/841
         if self.extract_and_run {
-            ctx.command_mut()
-                .arg("--setenv")
+            let arg = BwrapArg::new("--setenv")
                 .arg("APPIMAGE_EXTRACT_AND_RUN")
                 .arg("1");
+            ctx.args_mut().push(arg);
         }
         Ok(Scope::new())
     }
@@ -46,4 +66,12 @@ impl<C: Context> Service<C> for AppImageExtract {
     fn start(self: Box<Self>, _: &BwrapInfo) -> Result<HandleType, AppError> {
         Ok(HandleType::None)
     }
+
+    fn command(&self) -> Option<&Command> {
+        None
+    }
+
+    fn command_mut(&mut self) -> Option<&mut Command> {
+        None
+    }
 }
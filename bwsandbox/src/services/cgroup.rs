@@ -0,0 +1,190 @@
+use crate::{
+    error::AppError,
+    services::{BwrapInfo, Context, HandleType, Scope, Service},
+    system::{self, CgroupConfig},
+    utils,
+};
+use serde::Deserialize;
+use std::process::Command;
+
+const IO_WEIGHT: std::ops::RangeInclusive<u16> = 1..=10000;
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    // Needed for more than one entry, added to service name
+    pub name: Option<String>,
+    // Bytes or with K/M/G/T suffix, f.e. "4G"
+    memory_max: Option<Limit>,
+    pids_max: Option<Limit>,
+    // "$MAX $PERIOD" in microseconds, f.e. "50000 100000" for half of a single cpu
+    cpu_max: Option<String>,
+    io_weight: Option<u16>,
+}
+
+// Every limit accepts "max" to keep it unlimited
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Limit {
+    Num(u64),
+    Str(String),
+}
+
+impl Limit {
+    fn validate(&self, name: &str, suffixes: &[char]) -> Result<String, AppError> {
+        let value = match self {
+            Self::Num(v) => return Ok(v.to_string()),
+            Self::Str(v) => v,
+        };
+        let digits = value.strip_suffix(suffixes).unwrap_or(value);
+        if value == "max" || (!digits.is_empty() && digits.bytes().all(|v| v.is_ascii_digit())) {
+            return Ok(value.clone());
+        }
+        Err(AppError::Cgroup(format!("invalid {name} value {value:?}")))
+    }
+}
+
+impl Config {
+    pub fn instance(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn limits(&self) -> Result<Vec<(&'static str, String)>, AppError> {
+        let mut limits = Vec::new();
+        if let Some(value) = &self.memory_max {
+            let value = value.validate("memory_max", &['K', 'M', 'G', 'T'])?;
+            limits.push(("memory.max", value));
+        }
+        if let Some(value) = &self.pids_max {
+            limits.push(("pids.max", value.validate("pids_max", &[])?));
+        }
+        if let Some(value) = &self.cpu_max {
+            limits.push(("cpu.max", validate_cpu_max(value)?));
+        }
+        if let Some(value) = self.io_weight {
+            if !IO_WEIGHT.contains(&value) {
+                let reason = format!("io_weight must be in {IO_WEIGHT:?} range");
+                return Err(AppError::Cgroup(reason));
+            }
+            limits.push(("io.weight", value.to_string()));
+        }
+        Ok(limits)
+    }
+}
+
+fn validate_cpu_max(value: &str) -> Result<String, AppError> {
+    let mut parts = value.split(' ');
+    let quota = parts.next().unwrap_or_default();
+    let period = parts.next();
+
+    let is_num = |v: &str| v.parse::<u64>().is_ok_and(|v| v > 0);
+    let valid =
+        (quota == "max" || is_num(quota)) && period.is_none_or(is_num) && parts.next().is_none();
+    if valid {
+        Ok(value.to_owned())
+    } else {
+        Err(AppError::Cgroup(format!("invalid cpu_max value {value:?}")))
+    }
+}
+
+const NAME: &str = "cgroup";
+
+pub struct CgroupService {
+    name: String,
+    config: Option<CgroupConfig>,
+}
+
+impl CgroupService {
+    // Keep interface consistent
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn from_config(config: Config) -> Result<Self, AppError> {
+        let name = crate::services::instance_name(NAME, config.instance())?;
+        let limits = config.limits()?;
+        let root = system::cgroup::delegated_root()?;
+        let path = system::cgroup::sandbox_path(&root, utils::sandbox_id())?;
+        let config = CgroupConfig::new(path, limits);
+        Ok(Self {
+            name,
+            config: Some(config),
+        })
+    }
+
+    // Keep interface consistent
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn check_config(config: Config) -> Vec<AppError> {
+        let name = crate::services::instance_name(NAME, config.instance()).err();
+        let limits = config.limits().err();
+        let root = system::cgroup::delegated_root().err();
+        name.into_iter().chain(limits).chain(root).collect()
+    }
+}
+
+impl<C: Context> Service<C> for CgroupService {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply_before(&mut self, ctx: &mut C) -> Result<Scope, AppError> {
+        // Created on spawn, bwrap is moved in before sandboxed app is unblocked
+        if let Some(config) = self.config.take() {
+            ctx.set_cgroup(config);
+        }
+        Ok(Scope::new())
+    }
+
+    fn apply_after(&mut self, _: &mut C) -> Result<Scope, AppError> {
+        Ok(Scope::new())
+    }
+
+    fn start(self: Box<Self>, _: &BwrapInfo) -> Result<HandleType, AppError> {
+        Ok(HandleType::None)
+    }
+
+    fn command(&self) -> Option<&Command> {
+        None
+    }
+
+    fn command_mut(&mut self) -> Option<&mut Command> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits() {
+        let config: Config = toml::from_str(
+            r#"
+            memory_max = "4G"
+            pids_max = 1024
+            cpu_max = "50000 100000"
+            io_weight = 100
+            "#,
+        )
+        .unwrap();
+        let limits = config.limits().unwrap();
+        let expected = [
+            ("memory.max", "4G"),
+            ("pids.max", "1024"),
+            ("cpu.max", "50000 100000"),
+            ("io.weight", "100"),
+        ];
+        assert!(limits.iter().map(|(k, v)| (*k, v.as_str())).eq(expected));
+
+        let cases = [
+            r#"memory_max = "4GB""#,
+            r#"pids_max = "1K""#,
+            r#"cpu_max = "max 0""#,
+            r#"cpu_max = "50000 100000 1""#,
+            "io_weight = 0",
+        ];
+        for value in cases {
+            let config: Config = toml::from_str(value).unwrap();
+            assert!(config.limits().is_err(), "{value}");
+        }
+
+        let config: Config = toml::from_str(r#"pids_max = "max""#).unwrap();
+        assert!(config.limits().is_ok());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{error::AppError, services::Handle, system::ResourceUsage};
+use std::{
+    collections::BTreeSet,
+    fmt::Write as _,
+    io::{BufRead, BufReader, PipeReader},
+    path::PathBuf,
+    thread::JoinHandle,
+};
+
+// Always allowed by the proxy, calls to the bus itself are not part of a policy
+const BUS_NAME: &str = "org.freedesktop.DBus";
+const REQUEST_NAME: &str = "org.freedesktop.DBus.RequestName";
+
+// Names and methods seen in xdg-dbus-proxy --log output
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Learned {
+    talk: BTreeSet<String>,
+    call: BTreeSet<String>,
+    // Requested name is in message body, proxy does not log it
+    request_name: bool,
+}
+
+impl Learned {
+    // Client calls look like `C5: -> org.foo call org.foo.Iface.Method at /org/foo`
+    fn parse_line(&mut self, line: &str) {
+        let Some(rest) = line.strip_prefix('C') else {
+            return;
+        };
+        let Some((_, message)) = rest.split_once(": -> ") else {
+            return;
+        };
+        let parts: Vec<_> = message.split_whitespace().collect();
+        let [dest, "call", method, "at", path] = parts[..] else {
+            return;
+        };
+
+        if dest == BUS_NAME {
+            self.request_name |= method == REQUEST_NAME;
+            return;
+        }
+        // Unique names are per connection, useless in a profile
+        if dest.starts_with(':') || dest.starts_with('(') {
+            return;
+        }
+        self.talk.insert(dest.to_owned());
+        // Calls without interface are logged as `.Method`
+        let method = if method.starts_with('.') { "*" } else { method };
+        self.call.insert(format!("{dest}={method}@{path}"));
+    }
+
+    fn fragment(&self) -> String {
+        let mut out = String::from("# Suggested by bwsandbox dbus learn mode\n[dbus]\n");
+        let list = |out: &mut String, key: &str, items: &BTreeSet<String>, prefix: &str| {
+            let _ = writeln!(out, "{prefix}{key} = [");
+            for it in items {
+                let _ = writeln!(out, "{prefix}    {it:?},");
+            }
+            let _ = writeln!(out, "{prefix}]");
+        };
+        list(&mut out, "talk", &self.talk, "");
+        out.push_str("# Narrower alternative to talk, only methods called in this run\n");
+        list(&mut out, "call", &self.call, "# ");
+        if self.request_name {
+            out.push_str("# App requested a bus name, add names it owns to `own`\n");
+            out.push_str("# own = []\n");
+        }
+        out
+    }
+}
+
+// Proxy log is parsed until the proxy exits, fragment is written on stop
+#[derive(Debug)]
+pub struct LearnHandle {
+    child: Box<dyn Handle>,
+    parser: Option<JoinHandle<Learned>>,
+    path: PathBuf,
+}
+
+impl LearnHandle {
+    pub fn new(child: Box<dyn Handle>, log: PipeReader, path: PathBuf) -> Self {
+        let parser = std::thread::spawn(move || {
+            let mut learned = Learned::default();
+            for line in BufReader::new(log).lines().map_while(Result::ok) {
+                learned.parse_line(&line);
+            }
+            learned
+        });
+        Self {
+            child,
+            parser: Some(parser),
+            path,
+        }
+    }
+
+    fn write(&self, learned: &Learned) -> Result<(), AppError> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(AppError::file(dir))?;
+        }
+        std::fs::write(&self.path, learned.fragment()).map_err(AppError::file(&self.path))?;
+        log::warn!("D-Bus policy suggestion written to {}", self.path.display());
+        Ok(())
+    }
+}
+
+impl Handle for LearnHandle {
+    fn stop(&mut self) -> Result<(), AppError> {
+        self.child.stop()?;
+        let Some(parser) = self.parser.take() else {
+            return Ok(());
+        };
+        // Log pipe is closed once proxy is gone
+        let Ok(learned) = parser.join() else {
+            log::error!("D-Bus log parser panicked");
+            return Ok(());
+        };
+        self.write(&learned)
+    }
+
+    fn usage(&self) -> Option<ResourceUsage> {
+        self.child.usage()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_learn() {
+        let log = [
+            "C1: -> org.freedesktop.DBus call org.freedesktop.DBus.Hello at /org/freedesktop/DBus",
+            "C2: -> org.freedesktop.DBus call org.freedesktop.DBus.RequestName at /org/freedesktop/DBus",
+            "B1: <- org.freedesktop.DBus return from C1",
+            "C3: -> org.freedesktop.Notifications call org.freedesktop.Notifications.Notify at /org/freedesktop/Notifications",
+            "*HIDDEN* (ping)",
+            "C4: -> org.freedesktop.portal.Desktop call .Read at /org/freedesktop/portal/desktop",
+            "C5: -> :1.42 call org.app.Method at /",
+        ];
+        let mut learned = Learned::default();
+        for it in log {
+            learned.parse_line(it);
+        }
+        assert!(learned.request_name);
+        assert_eq!(
+            learned.talk.iter().collect::<Vec<_>>(),
+            [
+                "org.freedesktop.Notifications",
+                "org.freedesktop.portal.Desktop"
+            ]
+        );
+        assert!(
+            learned
+                .call
+                .contains("org.freedesktop.portal.Desktop=*@/org/freedesktop/portal/desktop")
+        );
+
+        // Fragment is a valid profile section
+        let fragment: toml::Table = toml::from_str(&learned.fragment()).unwrap();
+        let talk = fragment["dbus"]["talk"].as_array().unwrap();
+        assert_eq!(talk.len(), 2);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::bwrap::BwrapArg;
+use crate::config::{Cmd, EnvVal, TempFileVal};
+use crate::services::{BwrapInfo, Context, Handle, HandleType, Scope, Service, ServiceCommand};
+use crate::system::PollFile;
+use crate::{error::AppError, utils};
+use serde::Deserialize;
+use std::{
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+mod learn;
+mod policy;
+
+pub const NAME: &str = "xdg-dbus-proxy";
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Bus {
+    #[default]
+    Session,
+    System,
+}
+
+impl Bus {
+    fn address_env(self) -> &'static str {
+        match self {
+            Self::Session => "DBUS_SESSION_BUS_ADDRESS",
+            Self::System => "DBUS_SYSTEM_BUS_ADDRESS",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    // Needed for more than one proxy, f.e. `system`, added to service and proxy_bus names
+    pub name: Option<String>,
+    // Selects address variable set in sandbox
+    #[serde(default)]
+    pub bus: Bus,
+    pub user_bus: EnvVal<PathBuf>,
+    pub sandboxed_bus: EnvVal<PathBuf>,
+    pub proxy_bus: TempFileVal,
+    #[serde(flatten)]
+    pub policy: policy::Policy,
+    // Log traffic and write suggested policy on exit
+    #[serde(default)]
+    pub learn: bool,
+    // Default is <state dir>/dbus-learn/<sandbox id>[-<name>].toml
+    pub learn_file: Option<EnvVal<PathBuf>>,
+    #[serde(flatten)]
+    pub cmd: Cmd,
+}
+
+impl Config {
+    pub fn instance(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+#[derive(Debug)]
+pub struct DbusService {
+    name: String,
+    bus: Bus,
+    sandboxed_bus: PathBuf,
+    proxy_bus: PathBuf,
+    command: Command,
+    learn_file: Option<PathBuf>,
+}
+
+impl DbusService {
+    pub fn from_config(cfg: Config) -> Result<Self, AppError> {
+        let _ = utils::which_bin(utils::DBUS_CMD)?;
+        let name = crate::services::instance_name(NAME, cfg.instance())?;
+        let proxy_bus = match &cfg.name {
+            Some(instance) => cfg.proxy_bus.for_instance(instance),
+            None => cfg.proxy_bus,
+        };
+        let policy = cfg.policy.args()?;
+        let args = cfg.cmd.collect_args()?;
+        let learn_file = match (cfg.learn, cfg.learn_file) {
+            (false, _) => None,
+            (true, Some(path)) => Some(path.into_inner()),
+            (true, None) => Some(default_learn_file(cfg.name.as_deref())?),
+        };
+
+        let mut command = Command::new(utils::DBUS_CMD);
+        command
+            .arg(cfg.user_bus.as_inner())
+            .arg(proxy_bus.as_inner())
+            .args(policy)
+            .args(args);
+        if learn_file.is_some() {
+            command.arg("--log");
+        }
+
+        Ok(Self {
+            name,
+            bus: cfg.bus,
+            command,
+            learn_file,
+            sandboxed_bus: cfg.sandboxed_bus.into_inner(),
+            proxy_bus: proxy_bus.into_inner(),
+        })
+    }
+
+    // Keep interface consistent
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn check_config(cfg: Config) -> Vec<AppError> {
+        let bin = utils::which_bin(utils::DBUS_CMD).err();
+        let name = crate::services::instance_name(NAME, cfg.instance()).err();
+        let args = cfg.cmd.collect_args().err();
+        let policy = cfg.policy.check();
+        let learn = (cfg.learn && cfg.learn_file.is_none())
+            .then(|| default_learn_file(cfg.instance()).err())
+            .flatten();
+        bin.into_iter()
+            .chain(name)
+            .chain(args)
+            .chain(policy)
+            .chain(learn)
+            .collect()
+    }
+}
+
+// Instances write separate files, same as their proxy sockets
+fn default_learn_file(instance: Option<&str>) -> Result<PathBuf, AppError> {
+    let name = match instance {
+        Some(instance) => format!("{}-{instance}.toml", utils::sandbox_id()),
+        None => format!("{}.toml", utils::sandbox_id()),
+    };
+    Ok(utils::state_dir()?.join("dbus-learn").join(name))
+}
+
+impl<C: Context> Service<C> for DbusService {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply_before(&mut self, _ctx: &mut C) -> Result<Scope, AppError> {
+        Ok(Scope::new())
+    }
+
+    fn apply_after(&mut self, ctx: &mut C) -> Result<Scope, AppError> {
+        let arg = BwrapArg::new("--symlink")
+            .arg(&self.proxy_bus)
+            .arg(&self.sandboxed_bus);
+        ctx.args_mut().push(arg);
+        // Goes after profile args, so it wins over inherited host address
+        let mut address = std::ffi::OsString::from("unix:path=");
+        address.push(&self.sandboxed_bus);
+        let arg = BwrapArg::new("--setenv")
+            .arg(self.bus.address_env())
+            .arg(address);
+        ctx.args_mut().push(arg);
+        Ok(Scope::new().remove_file(&self.proxy_bus))
+    }
+
+    fn start(mut self: Box<Self>, info: &BwrapInfo) -> Result<HandleType, AppError> {
+        crate::print_command::print_command(&self.command);
+        // Proxy logs to stdout, captured output is replaced in learn mode
+        let log = match &self.learn_file {
+            Some(_) => {
+                let (reader, writer) =
+                    std::io::pipe().map_err(AppError::io("Failed to create pipe"))?;
+                self.command.stdout(writer);
+                Some(reader)
+            }
+            None => None,
+        };
+        let child = self
+            .command
+            .stdin(Stdio::null())
+            .spawn_service()
+            .map_err(AppError::spawn(self.name))?;
+        // Drop pipe writer held by command, parser gets EOF once proxy exits
+        self.command.stdout(Stdio::null());
+        let handle = match (log, self.learn_file.take()) {
+            (Some(log), Some(path)) => {
+                let child: Box<dyn Handle> = Box::new(child);
+                HandleType::new(learn::LearnHandle::new(child, log, path))
+            }
+            _ => HandleType::new(child),
+        };
+        PollFile::watch(&self.proxy_bus)?.wait_exists(info.ready_timeout)?;
+        Ok(handle)
+    }
+
+    fn command(&self) -> Option<&Command> {
+        Some(&self.command)
+    }
+
+    fn command_mut(&mut self) -> Option<&mut Command> {
+        Some(&mut self.command)
+    }
+}
@@ -0,0 +1,206 @@
+use crate::error::AppError;
+use serde::Deserialize;
+
+// Limits from D-Bus specification
+const MAX_NAME_LEN: usize = 255;
+
+// Typed xdg-dbus-proxy rules, see xdg-dbus-proxy(1)
+#[derive(Debug, Default, Deserialize)]
+pub struct Policy {
+    // Enabled when any rule is set, `filter = true` without rules denies everything
+    pub filter: Option<bool>,
+    #[serde(default)]
+    pub see: Vec<String>,
+    #[serde(default)]
+    pub talk: Vec<String>,
+    #[serde(default)]
+    pub own: Vec<String>,
+    // `name=rule@path`, f.e. `org.freedesktop.portal.*=*@/org/freedesktop/portal/desktop`
+    #[serde(default)]
+    pub call: Vec<String>,
+    #[serde(default)]
+    pub broadcast: Vec<String>,
+}
+
+impl Policy {
+    fn has_rules(&self) -> bool {
+        [
+            &self.see,
+            &self.talk,
+            &self.own,
+            &self.call,
+            &self.broadcast,
+        ]
+        .iter()
+        .any(|v| !v.is_empty())
+    }
+
+    pub fn check(&self) -> Vec<AppError> {
+        let mut problems = Vec::new();
+        if self.filter == Some(false) && self.has_rules() {
+            let reason = "rules are ignored with filter = false".to_owned();
+            problems.push(AppError::DbusPolicy(reason));
+        }
+
+        let names = [("see", &self.see), ("talk", &self.talk), ("own", &self.own)];
+        for (kind, list) in names {
+            for it in list {
+                if !is_name_pattern(it) {
+                    let reason = format!("{kind} {it:?} is not a bus name");
+                    problems.push(AppError::DbusPolicy(reason));
+                }
+            }
+        }
+        for (kind, list) in [("call", &self.call), ("broadcast", &self.broadcast)] {
+            for it in list {
+                if let Err(reason) = check_rule(it) {
+                    let reason = format!("{kind} {it:?}: {reason}");
+                    problems.push(AppError::DbusPolicy(reason));
+                }
+            }
+        }
+        problems
+    }
+
+    // Args for xdg-dbus-proxy, problems are reported before anything is launched
+    pub fn args(&self) -> Result<Vec<String>, AppError> {
+        if let Some(problem) = self.check().into_iter().next() {
+            return Err(problem);
+        }
+
+        let mut args = Vec::new();
+        if self.filter.unwrap_or_else(|| self.has_rules()) {
+            args.push("--filter".to_owned());
+        }
+        let lists = [
+            ("see", &self.see),
+            ("talk", &self.talk),
+            ("own", &self.own),
+            ("call", &self.call),
+            ("broadcast", &self.broadcast),
+        ];
+        for (kind, list) in lists {
+            args.extend(list.iter().map(|v| format!("--{kind}={v}")));
+        }
+        Ok(args)
+    }
+}
+
+// Well-known name, `.*` suffix matches the name and every name below it
+fn is_name_pattern(value: &str) -> bool {
+    let name = value.strip_suffix(".*").unwrap_or(value);
+    is_name(name, true)
+}
+
+// Elements of [A-Za-z0-9_] not starting with digit, `-` allowed only in bus names
+fn is_name(value: &str, bus: bool) -> bool {
+    let elements: Vec<_> = value.split('.').collect();
+    value.len() <= MAX_NAME_LEN
+        && elements.len() >= 2
+        && elements.iter().all(|v| {
+            let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || (bus && c == '-');
+            v.chars().next().is_some_and(|c| !c.is_ascii_digit()) && v.chars().all(valid)
+        })
+}
+
+// `[METHOD][@PATH]`, METHOD is `*`, `interface.*` or `interface.method`, PATH may end with `/*`
+fn check_rule(value: &str) -> Result<(), String> {
+    let Some((name, rule)) = value.split_once('=') else {
+        return Err("expected name=rule".into());
+    };
+    if !is_name_pattern(name) {
+        return Err(format!("{name:?} is not a bus name"));
+    }
+
+    let (method, path) = match rule.split_once('@') {
+        Some((method, path)) => (method, Some(path)),
+        None => (rule, None),
+    };
+    let method_valid = match method {
+        "" | "*" => true,
+        _ => match method.strip_suffix(".*") {
+            Some(interface) => is_name(interface, false),
+            None => method
+                .rsplit_once('.')
+                .is_some_and(|(interface, member)| is_name(interface, false) && is_member(member)),
+        },
+    };
+    if !method_valid {
+        return Err(format!("{method:?} is not a method or interface"));
+    }
+
+    if let Some(path) = path {
+        // `/*` matches every object
+        let object = match path.strip_suffix("/*") {
+            Some("") => "/",
+            Some(v) => v,
+            None => path,
+        };
+        if !is_object_path(object) {
+            return Err(format!("{path:?} is not an object path"));
+        }
+    }
+    Ok(())
+}
+
+fn is_member(value: &str) -> bool {
+    value.chars().next().is_some_and(|c| !c.is_ascii_digit())
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_object_path(value: &str) -> bool {
+    if value == "/" {
+        return true;
+    }
+    let Some(rest) = value.strip_prefix('/') else {
+        return false;
+    };
+    rest.split('/')
+        .all(|v| !v.is_empty() && v.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy() {
+        let policy: Policy = toml::from_str(
+            r#"
+            talk = ["org.freedesktop.portal.*", "org.freedesktop.Notifications"]
+            own = ["org.mpris.MediaPlayer2.app"]
+            call = ["org.freedesktop.portal.*=*@/org/freedesktop/portal/desktop/*"]
+            broadcast = ["org.freedesktop.portal.*=org.freedesktop.portal.Settings.SettingChanged@/*"]
+            "#,
+        )
+        .unwrap();
+        assert!(policy.check().is_empty(), "{:?}", policy.check());
+        let args = policy.args().unwrap();
+        assert_eq!(args[0], "--filter");
+        assert_eq!(args[1], "--talk=org.freedesktop.portal.*");
+        assert_eq!(args.len(), 6);
+
+        assert!(Policy::default().args().unwrap().is_empty());
+        let policy = Policy {
+            filter: Some(true),
+            ..Policy::default()
+        };
+        assert_eq!(policy.args().unwrap(), ["--filter"]);
+
+        let broken = Policy {
+            filter: Some(false),
+            talk: vec!["org.freedesktop.portal".into(), "org..portal".into()],
+            own: vec!["1org.app".into()],
+            call: vec![
+                "org.app=org.app.Method@relative".into(),
+                "org.app=Method".into(),
+                "org.app=org.app.*@/ok/*".into(),
+                "org.app".into(),
+                "org.app=*@".into(),
+            ],
+            ..Policy::default()
+        };
+        // filter, talk, own and four calls
+        assert_eq!(broken.check().len(), 7, "{:?}", broken.check());
+    }
+}
@@ -1,9 +1,17 @@
+use crate::bwrap::BwrapArg;
 use crate::error::AppError;
 use crate::services::{BwrapInfo, Context, HandleType, Scope, Service};
 use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+const NAME: &str = "env_mapper";
 
 #[derive(Debug, Deserialize, Serialize, Default)]
 pub struct EnvMapper {
+    // Needed for more than one mapper, added to service name
+    name: Option<String>,
+    #[serde(skip)]
+    service_name: String,
     #[serde(default = "unset_all_default")]
     unset_all: bool,
     #[serde(default)]
@@ -13,12 +21,23 @@ pub struct EnvMapper {
 }
 
 impl EnvMapper {
-    // Used as build callack for same methods
-    // Warning ignored to keep consistent across other services logic
-    #[allow(clippy::unnecessary_wraps)]
-    pub fn from_config(config: Self) -> Result<Self, AppError> {
+    pub fn from_config(mut config: Self) -> Result<Self, AppError> {
+        config.service_name = crate::services::instance_name(NAME, config.instance())?;
         Ok(config)
     }
+
+    // Keep interface consistent
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn check_config(config: Self) -> Vec<AppError> {
+        crate::services::instance_name(NAME, config.instance())
+            .err()
+            .into_iter()
+            .collect()
+    }
+
+    pub fn instance(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
 }
 
 fn unset_all_default() -> bool {
@@ -26,23 +45,24 @@ fn unset_all_default() -> bool {
 }
 
 impl<C: Context> Service<C> for EnvMapper {
-    fn name(&self) -> &'static str {
-        "env_mapper"
+    fn name(&self) -> &str {
+        &self.service_name
     }
 
     fn apply_before(&mut self, ctx: &mut C) -> Result<Scope, AppError> {
         if self.unset_all {
-            ctx.command_mut().arg("--clearenv");
+            ctx.args_mut().push(BwrapArg::new("--clearenv"));
         }
 
         for it in &self.keep {
             if let Ok(v) = std::env::var(it) {
-                ctx.command_mut().arg("--setenv").arg(it).arg(v);
+                ctx.args_mut()
+                    .push(BwrapArg::new("--setenv").arg(it).arg(v));
             }
         }
 
         for it in &self.unset {
-            ctx.command_mut().arg("--unsetenv").arg(it);
+            ctx.args_mut().push(BwrapArg::new("--unsetenv").arg(it));
         }
 
         Ok(Scope::new())
@@ -55,4 +75,12 @@ impl<C: Context> Service<C> for EnvMapper {
     fn start(self: Box<Self>, _: &BwrapInfo) -> Result<HandleType, AppError> {
         Ok(HandleType::None)
     }
+
+    fn command(&self) -> Option<&Command> {
+        None
+    }
+
+    fn command_mut(&mut self) -> Option<&mut Command> {
+        None
+    }
 }
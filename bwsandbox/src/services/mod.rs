@@ -1,65 +1,393 @@
 mod types;
+use net::NET_SERVICES;
+
+// Sections of services which wait for helper readiness, with their service names
+pub const READY_SERVICES: [(&str, &str); 3] = [
+    ("dbus", dbus::NAME),
+    ("pasta", net::pasta::NAME),
+    ("slirp4netns", net::slirp4netns::NAME),
+];
 pub use types::*;
 
 mod appimage;
+mod cgroup;
 mod dbus;
 mod env_mapper;
 mod net;
 mod nix;
+mod order;
+mod process;
 mod seccomp;
 mod wl;
 
-use crate::error::AppError;
+use crate::{config::Ordered, error::AppError};
+use order::Node;
 use serde::de::DeserializeOwned;
 
 type ServiceBuilder<C, S> = fn(C) -> Result<S, AppError>;
-type EntryConfig<C> = Option<crate::config::Entry<C>>;
+type ServiceCheck<C> = fn(C) -> Vec<AppError>;
+type EntriesConfig<C> = Option<crate::config::Entries<C>>;
+type InstanceName<C> = fn(&C) -> Option<&str>;
 type BoxedService<C> = Box<dyn Service<C>>;
 
+// Every service is a table or array of tables, f.e. session and system bus proxies
 #[derive(Debug, serde::Deserialize)]
 pub struct ServicesConfig {
-    dbus: EntryConfig<dbus::Config>,
-    env_mapper: EntryConfig<env_mapper::EnvMapper>,
-    seccomp: EntryConfig<seccomp::Config>,
-    slirp4netns: EntryConfig<net::slirp4netns::Config>,
-    appimage: EntryConfig<appimage::AppImageExtract>,
-    pasta: EntryConfig<net::pasta::Config>,
-    wl_security_context_v1: EntryConfig<wl::Config>,
-    nix: EntryConfig<nix::Config>,
+    dbus: EntriesConfig<dbus::Config>,
+    env_mapper: EntriesConfig<env_mapper::EnvMapper>,
+    seccomp: EntriesConfig<seccomp::Config>,
+    slirp4netns: EntriesConfig<net::slirp4netns::Config>,
+    appimage: EntriesConfig<appimage::AppImageExtract>,
+    pasta: EntriesConfig<net::pasta::Config>,
+    wl_security_context_v1: EntriesConfig<wl::Config>,
+    nix: EntriesConfig<nix::Config>,
+    cgroup: EntriesConfig<cgroup::Config>,
+    process: EntriesConfig<process::Config>,
 }
 
 impl ServicesConfig {
-    pub fn load<C: Context>(self) -> Result<Vec<BoxedService<C>>, AppError> {
+    pub fn load<C: Context + 'static>(self) -> Result<Vec<BoxedService<C>>, AppError> {
         log::info!("---- initializing services ----");
-        let services = vec![
-            Self::load_single(self.dbus, dbus::DbusService::from_config)?,
-            Self::load_single(self.env_mapper, env_mapper::EnvMapper::from_config)?,
-            Self::load_single(self.seccomp, seccomp::SeccompService::from_config)?,
-            Self::load_single(self.slirp4netns, net::slirp4netns::Slirp4netns::from_config)?,
-            Self::load_single(self.appimage, appimage::AppImageExtract::from_config)?,
-            Self::load_single(self.pasta, net::pasta::Pasta::from_config)?,
-            Self::load_single(
+        let mut nodes = Self::load_many(
+            "dbus",
+            self.dbus,
+            dbus::Config::instance,
+            dbus::DbusService::from_config,
+        )?;
+        nodes.extend(Self::load_many(
+            "env_mapper",
+            self.env_mapper,
+            env_mapper::EnvMapper::instance,
+            env_mapper::EnvMapper::from_config,
+        )?);
+        nodes.extend(Self::load_many(
+            "seccomp",
+            self.seccomp,
+            seccomp::Config::instance,
+            seccomp::SeccompService::from_config,
+        )?);
+        nodes.extend(Self::load_many(
+            "slirp4netns",
+            self.slirp4netns,
+            net::slirp4netns::Config::instance,
+            net::slirp4netns::Slirp4netns::from_config,
+        )?);
+        nodes.extend(Self::load_many(
+            "appimage",
+            self.appimage,
+            appimage::AppImageExtract::instance,
+            appimage::AppImageExtract::from_config,
+        )?);
+        nodes.extend(Self::load_many(
+            "pasta",
+            self.pasta,
+            net::pasta::Config::instance,
+            net::pasta::Pasta::from_config,
+        )?);
+        nodes.extend(Self::load_many(
+            "wl_security_context_v1",
+            self.wl_security_context_v1,
+            wl::Config::instance,
+            wl::SecurityContextV1::from_config,
+        )?);
+        nodes.extend(Self::load_many(
+            "nix",
+            self.nix,
+            nix::Config::instance,
+            nix::NixMapper::from_config,
+        )?);
+        nodes.extend(Self::load_many(
+            "cgroup",
+            self.cgroup,
+            cgroup::Config::instance,
+            cgroup::CgroupService::from_config,
+        )?);
+        nodes.extend(Self::load_many(
+            "process",
+            self.process,
+            process::Config::instance,
+            process::HelperProcess::from_config,
+        )?);
+
+        // Names key ready timeouts, output logs and exit report
+        let names = nodes.iter().map(|v| Service::name(v.item.as_ref()));
+        if let Some(name) = duplicate(names) {
+            let reason = format!("{name:?} is used twice, set distinct `name` for every instance");
+            return Err(AppError::Instance(reason));
+        }
+
+        let sorted = order::sort(nodes).map_err(|(_, e)| e)?;
+        let names: Vec<_> = sorted
+            .iter()
+            .map(|v| Service::name(v.0.as_ref()).to_owned())
+            .collect();
+        log::info!("Services order: {names:?}");
+        let services = sorted.into_iter().map(|(service, wait)| {
+            if wait.is_empty() {
+                return service;
+            }
+            let after = wait.into_iter().map(|v| names[v].clone()).collect();
+            Box::new(order::Dependent::new(service, after)) as BoxedService<C>
+        });
+        Ok(services.collect())
+    }
+
+    // Order references span sections, so it is checked on the whole profile
+    pub fn check_order(self) -> Vec<(&'static str, AppError)> {
+        let mut nodes = Self::order_nodes(
+            "dbus",
+            self.dbus.into_iter().flatten(),
+            dbus::Config::instance,
+        );
+        nodes.extend(Self::order_nodes(
+            "env_mapper",
+            self.env_mapper.into_iter().flatten(),
+            env_mapper::EnvMapper::instance,
+        ));
+        nodes.extend(Self::order_nodes(
+            "seccomp",
+            self.seccomp.into_iter().flatten(),
+            seccomp::Config::instance,
+        ));
+        nodes.extend(Self::order_nodes(
+            "slirp4netns",
+            self.slirp4netns.into_iter().flatten(),
+            net::slirp4netns::Config::instance,
+        ));
+        nodes.extend(Self::order_nodes(
+            "appimage",
+            self.appimage.into_iter().flatten(),
+            appimage::AppImageExtract::instance,
+        ));
+        nodes.extend(Self::order_nodes(
+            "pasta",
+            self.pasta.into_iter().flatten(),
+            net::pasta::Config::instance,
+        ));
+        nodes.extend(Self::order_nodes(
+            "wl_security_context_v1",
+            self.wl_security_context_v1.into_iter().flatten(),
+            wl::Config::instance,
+        ));
+        nodes.extend(Self::order_nodes(
+            "nix",
+            self.nix.into_iter().flatten(),
+            nix::Config::instance,
+        ));
+        nodes.extend(Self::order_nodes(
+            "cgroup",
+            self.cgroup.into_iter().flatten(),
+            cgroup::Config::instance,
+        ));
+        nodes.extend(Self::order_nodes(
+            "process",
+            self.process.into_iter().flatten(),
+            process::Config::instance,
+        ));
+        order::sort(nodes).err().into_iter().collect()
+    }
+
+    // Validate configs without starting anything, all found problems returned at once
+    pub fn check(self) -> Vec<(&'static str, AppError)> {
+        let mut problems = Vec::new();
+        let mut push = |name, errors: Vec<AppError>| {
+            problems.extend(errors.into_iter().map(|e| (name, e)));
+        };
+
+        push(
+            "dbus",
+            Self::check_many(
+                self.dbus,
+                dbus::DbusService::check_config,
+                dbus::Config::instance,
+            ),
+        );
+        push(
+            "env_mapper",
+            Self::check_many(
+                self.env_mapper,
+                env_mapper::EnvMapper::check_config,
+                env_mapper::EnvMapper::instance,
+            ),
+        );
+        push(
+            "seccomp",
+            Self::check_many(
+                self.seccomp,
+                seccomp::SeccompService::check_config,
+                seccomp::Config::instance,
+            ),
+        );
+        push(
+            "slirp4netns",
+            Self::check_many(
+                self.slirp4netns,
+                net::slirp4netns::Slirp4netns::check_config,
+                net::slirp4netns::Config::instance,
+            ),
+        );
+        push(
+            "appimage",
+            Self::check_many(
+                self.appimage,
+                appimage::AppImageExtract::check_config,
+                appimage::AppImageExtract::instance,
+            ),
+        );
+        push(
+            "pasta",
+            Self::check_many(
+                self.pasta,
+                net::pasta::Pasta::check_config,
+                net::pasta::Config::instance,
+            ),
+        );
+        push(
+            "wl_security_context_v1",
+            Self::check_many(
                 self.wl_security_context_v1,
-                wl::SecurityContextV1::from_config,
-            )?,
-            Self::load_single(self.nix, nix::NixMapper::from_config)?,
-        ];
+                wl::SecurityContextV1::check_config,
+                wl::Config::instance,
+            ),
+        );
+        push(
+            "nix",
+            Self::check_many(
+                self.nix,
+                nix::NixMapper::check_config,
+                nix::Config::instance,
+            ),
+        );
+        push(
+            "cgroup",
+            Self::check_many(
+                self.cgroup,
+                cgroup::CgroupService::check_config,
+                cgroup::Config::instance,
+            ),
+        );
+        push(
+            "process",
+            Self::check_many(
+                self.process,
+                process::HelperProcess::check_config,
+                process::Config::instance,
+            ),
+        );
+
+        problems
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dbus.is_none()
+            && self.env_mapper.is_none()
+            && self.seccomp.is_none()
+            && self.slirp4netns.is_none()
+            && self.appimage.is_none()
+            && self.pasta.is_none()
+            && self.wl_security_context_v1.is_none()
+            && self.nix.is_none()
+            && self.cgroup.is_none()
+            && self.process.is_none()
+    }
+
+    fn check_many<C: DeserializeOwned>(
+        cfg: EntriesConfig<C>,
+        check: ServiceCheck<C>,
+        instance: InstanceName<C>,
+    ) -> Vec<AppError> {
+        let mut problems = Vec::new();
+        let mut configs = Vec::new();
+        for it in cfg.into_iter().flatten() {
+            match it.load() {
+                Ok((_, config)) => configs.push(config),
+                Err(e) => problems.push(e),
+            }
+        }
 
-        let services = services.into_iter().flatten().collect();
-        Ok(services)
+        let names = configs.iter().map(|v| instance(v).unwrap_or_default());
+        if let Some(name) = duplicate(names) {
+            let reason = format!("{name:?} is used twice, set distinct `name` for every instance");
+            problems.push(AppError::Instance(reason));
+        }
+        problems.extend(configs.into_iter().flat_map(check));
+        problems
+    }
+
+    // Broken configs are skipped, they are reported by check
+    fn order_nodes<C: DeserializeOwned>(
+        section: &'static str,
+        cfg: impl IntoIterator<Item = Ordered<C>>,
+        instance: InstanceName<C>,
+    ) -> Vec<Node<()>> {
+        let configs = cfg.into_iter().filter_map(|v| v.load().ok());
+        let nodes = configs.map(|(order, config)| Node {
+            section,
+            instance: instance(&config).map(String::from),
+            order,
+            item: (),
+        });
+        nodes.collect()
     }
 
-    fn load_single<Ctx: Context, C: DeserializeOwned, S: Service<Ctx> + 'static>(
-        cfg: EntryConfig<C>,
+    fn load_many<Ctx: Context, C: DeserializeOwned, S: Service<Ctx> + 'static>(
+        section: &'static str,
+        cfg: EntriesConfig<C>,
+        instance: InstanceName<C>,
         build: ServiceBuilder<C, S>,
-    ) -> Result<Option<BoxedService<Ctx>>, AppError> {
-        if let Some(entry) = cfg {
-            let config = entry.load(crate::utils::deserialize)?;
-            let service = build(config)?;
-            log::info!("'{}' initialized", service.name());
-            let service = Box::new(service) as BoxedService<Ctx>;
-            return Ok(Some(service));
+    ) -> Result<Vec<Node<BoxedService<Ctx>>>, AppError> {
+        let mut nodes = Vec::new();
+        for it in cfg.into_iter().flatten() {
+            let (order, config) = it.load()?;
+            let instance = instance(&config).map(String::from);
+            nodes.push(Self::build(section, instance, order, config, build)?);
         }
-        Ok(None)
+        Ok(nodes)
+    }
+
+    fn build<Ctx: Context, C, S: Service<Ctx> + 'static>(
+        section: &'static str,
+        instance: Option<String>,
+        order: crate::config::Order,
+        config: C,
+        build: ServiceBuilder<C, S>,
+    ) -> Result<Node<BoxedService<Ctx>>, AppError> {
+        let service = build(config)?;
+        log::info!("'{}' initialized", service.name());
+        Ok(Node {
+            section,
+            instance,
+            order,
+            item: Box::new(service),
+        })
+    }
+}
+
+fn duplicate<'a>(names: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let mut seen = std::collections::HashSet::new();
+    names.into_iter().find(|v| !seen.insert(*v))
+}
+
+// Service itself or any of its `<service> <instance>` instances
+pub fn is_instance_of(name: &str, service: &str) -> bool {
+    name.strip_prefix(service)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+}
+
+// Any pasta or slirp4netns instance
+pub fn is_net_service(name: &str) -> bool {
+    NET_SERVICES.iter().any(|v| is_instance_of(name, v))
+}
+
+// Instance name is a part of service name and temp file names
+pub fn instance_name(service: &str, instance: Option<&str>) -> Result<String, AppError> {
+    let Some(instance) = instance else {
+        return Ok(service.into());
+    };
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    if instance.is_empty() || !instance.chars().all(valid) {
+        let reason = format!("{service} name {instance:?} must be [A-Za-z0-9_-]+");
+        return Err(AppError::Instance(reason));
     }
+    Ok(format!("{service} {instance}"))
 }
@@ -1,5 +1,8 @@
-mod nsfix;
+pub(super) mod nsfix;
 mod resolv_conf;
 
 pub mod pasta;
 pub mod slirp4netns;
+
+// Services which unshare sandbox network and provide their own
+pub const NET_SERVICES: [&str; 2] = [pasta::NAME, slirp4netns::NAME];
@@ -1,12 +1,16 @@
 use super::resolv_conf::{ResolvConf, ResolvConfVal};
 use crate::services::{BwrapInfo, Context, HandleType, Scope, Service, ServiceCommand};
 use crate::system::PollFile;
-use crate::{config::Cmd, error::AppError, utils};
+use crate::{bwrap::BwrapArg, config::Cmd, error::AppError, utils};
 use serde::Deserialize;
 use std::process::{Command, Stdio};
 
+pub const NAME: &str = "pasta network";
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
+    // Needed for more than one entry, added to service name and resolv.conf file
+    pub name: Option<String>,
     #[serde(default)]
     pub resolv_conf: ResolvConfVal,
     #[serde(default = "default_quiet")]
@@ -15,12 +19,19 @@ pub struct Config {
     pub cmd: Cmd,
 }
 
+impl Config {
+    pub fn instance(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
 fn default_quiet() -> bool {
     true
 }
 
 #[derive(Debug)]
 pub struct Pasta {
+    name: String,
     command: Command,
     resolv_conf: ResolvConf,
     with_dev: bool,
@@ -29,6 +40,7 @@ pub struct Pasta {
 impl Pasta {
     pub fn from_config(config: Config) -> Result<Self, AppError> {
         let _ = utils::which_bin(utils::PASTA_CMD)?;
+        let name = crate::services::instance_name(NAME, config.instance())?;
         let args = config.cmd.collect_args()?;
         let mut command = Command::new(utils::PASTA_CMD);
 
@@ -40,35 +52,48 @@ impl Pasta {
             command.stderr(Stdio::null());
         }
 
-        let resolv_conf = config.resolv_conf.generate()?;
+        let resolv_conf = config.resolv_conf.generate(config.name.as_deref());
         Ok(Self {
+            name,
             command,
             resolv_conf,
             with_dev: false,
         })
     }
+
+    // Keep interface consistent
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn check_config(config: Config) -> Vec<AppError> {
+        let name = crate::services::instance_name(NAME, config.instance()).err();
+        let bin = utils::which_bin(utils::PASTA_CMD).err();
+        let args = config.cmd.collect_args().err();
+        name.into_iter().chain(bin).chain(args).collect()
+    }
 }
 
 impl<C: Context> Service<C> for Pasta {
-    fn name(&self) -> &'static str {
-        "pasta network"
+    fn name(&self) -> &str {
+        &self.name
     }
 
     fn apply_before(&mut self, ctx: &mut C) -> Result<Scope, AppError> {
-        // TODO: Find better solution to avoid datarace, iterating ALL args can be pretty slow and error prone
-        self.with_dev = ctx.arg_exist_before("--dev");
+        self.with_dev = ctx.args().iter().any(|v| v.is("--dev"));
         Ok(Scope::new())
     }
 
     fn apply_after(&mut self, ctx: &mut C) -> Result<Scope, AppError> {
         // Probably net should be unshared in bwrap if user want to use slirp4netns
-        ctx.command_mut().arg("--unshare-net");
+        ctx.args_mut().push(BwrapArg::new("--unshare-net"));
 
         // Mount resolv conf
-        let scope = self.resolv_conf.mount(ctx.command_mut(), Scope::new());
+        let scope = self.resolv_conf.mount(ctx.args_mut(), Scope::new());
         Ok(scope)
     }
 
+    fn prepare(&mut self) -> Result<(), AppError> {
+        self.resolv_conf.write()
+    }
+
     fn start(mut self: Box<Self>, info: &BwrapInfo) -> Result<HandleType, AppError> {
         let pasta_pid = utils::temp_dir().join("pasta.pid");
         self.command.arg("--pid").arg(&pasta_pid);
@@ -87,7 +112,15 @@ impl<C: Context> Service<C> for Pasta {
             .spawn_service()
             .map_err(AppError::spawn(utils::PASTA_CMD))?;
 
-        PollFile::watch(&pasta_pid)?.wait_exists(utils::READY_TIMEOUT)?;
+        PollFile::watch(&pasta_pid)?.wait_exists(info.ready_timeout)?;
         Ok(HandleType::new(child))
     }
+
+    fn command(&self) -> Option<&Command> {
+        Some(&self.command)
+    }
+
+    fn command_mut(&mut self) -> Option<&mut Command> {
+        Some(&mut self.command)
+    }
 }
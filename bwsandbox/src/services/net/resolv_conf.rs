@@ -1,32 +1,46 @@
-use crate::{error::AppError, services::Scope, utils};
+use crate::{
+    bwrap::{BwrapArg, BwrapArgs},
+    config::TempFileVal,
+    error::AppError,
+    services::Scope,
+};
 use serde::Deserialize;
-use std::{path::PathBuf, process::Command};
+use std::path::PathBuf;
 
 #[derive(Debug, Deserialize, Default)]
 pub struct ResolvConfVal(Option<String>);
 impl ResolvConfVal {
-    pub fn generate(self) -> Result<ResolvConf, AppError> {
-        let file = match self.0 {
-            Some(v) => {
-                let file = utils::temp_dir().join("resolv.conf");
-                log::trace!("Create {}", file.display());
-                std::fs::write(&file, v).map_err(AppError::file(&file))?;
-                Some(file)
-            }
-            None => None,
-        };
-        Ok(ResolvConf(file))
+    // Every instance of a service gets own file
+    pub fn generate(self, instance: Option<&str>) -> ResolvConf {
+        let file = self.0.map(|v| {
+            let path = TempFileVal::new("resolv.conf");
+            let path = match instance {
+                Some(instance) => path.for_instance(instance),
+                None => path,
+            };
+            (path.into_inner(), v)
+        });
+        ResolvConf(file)
     }
 }
 
 #[derive(Debug)]
-pub struct ResolvConf(Option<PathBuf>);
+pub struct ResolvConf(Option<(PathBuf, String)>);
 impl ResolvConf {
-    pub fn mount(&self, command: &mut Command, mut scope: Scope) -> Scope {
-        if let Some(path) = &self.0 {
-            command.arg("--ro-bind").arg(path).arg("/etc/resolv.conf");
+    pub fn mount(&self, args: &mut BwrapArgs, mut scope: Scope) -> Scope {
+        if let Some((path, _)) = &self.0 {
+            args.push(BwrapArg::new("--ro-bind").arg(path).arg("/etc/resolv.conf"));
             scope = scope.remove_file(path);
         }
         scope
     }
+
+    // Mounted path is known before, file itself is written only for real launch
+    pub fn write(&self) -> Result<(), AppError> {
+        if let Some((path, content)) = &self.0 {
+            log::trace!("Create {}", path.display());
+            std::fs::write(path, content).map_err(AppError::file(path))?;
+        }
+        Ok(())
+    }
 }
@@ -3,6 +3,8 @@ use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
+    // Needed for more than one entry, added to service name and resolv.conf file
+    pub name: Option<String>,
     #[serde(default = "default_if_name")]
     pub if_name: String,
     #[serde(default)]
@@ -13,6 +15,12 @@ pub struct Config {
     pub cmd: Cmd,
 }
 
+impl Config {
+    pub fn instance(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
 fn default_if_name() -> String {
     "tap0".into()
 }
@@ -3,3 +3,5 @@ mod service;
 
 pub use config::Config;
 pub use service::Slirp4netns;
+
+pub const NAME: &str = "slirp4netns network";
@@ -2,10 +2,11 @@ use super::config::Config;
 use crate::services::net::{nsfix, resolv_conf::ResolvConf};
 use crate::services::{BwrapInfo, Context, HandleType, Scope, Service, ServiceCommand};
 use crate::system::{AsFdArg, ReadExt, SharedPipe};
-use crate::{error::AppError, utils};
+use crate::{bwrap::BwrapArg, error::AppError, utils};
 use std::process::{Command, Stdio};
 
 pub struct Slirp4netns {
+    name: String,
     command: Command,
     ready: SharedPipe,
     with_dev: bool,
@@ -17,6 +18,7 @@ pub struct Slirp4netns {
 impl Slirp4netns {
     pub fn from_config(config: Config) -> Result<Self, AppError> {
         let _ = utils::which_bin(utils::SLIRP4NETNS_CMD)?;
+        let name = crate::services::instance_name(super::NAME, config.instance())?;
         let args = config.cmd.collect_args()?;
         let mut command = Command::new(utils::SLIRP4NETNS_CMD);
         command.args(args);
@@ -26,9 +28,10 @@ impl Slirp4netns {
             command.stderr(Stdio::null());
         }
 
-        let resolv_conf = config.resolv_conf.generate()?;
+        let resolv_conf = config.resolv_conf.generate(config.name.as_deref());
         let ready = SharedPipe::new()?;
         Ok(Self {
+            name,
             command,
             ready,
             with_dev: false,
@@ -36,30 +39,44 @@ impl Slirp4netns {
             if_name: config.if_name,
         })
     }
+
+    // Keep interface consistent
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn check_config(config: Config) -> Vec<AppError> {
+        let name = crate::services::instance_name(super::NAME, config.instance()).err();
+        let bin = utils::which_bin(utils::SLIRP4NETNS_CMD).err();
+        let args = config.cmd.collect_args().err();
+        name.into_iter().chain(bin).chain(args).collect()
+    }
 }
 
 impl<C: Context> Service<C> for Slirp4netns {
-    fn name(&self) -> &'static str {
-        "slirp4netns network"
+    fn name(&self) -> &str {
+        &self.name
     }
 
     fn apply_before(&mut self, ctx: &mut C) -> Result<Scope, AppError> {
-        self.with_dev = ctx.arg_exist_before("--dev");
+        self.with_dev = ctx.args().iter().any(|v| v.is("--dev"));
         Ok(Scope::new())
     }
 
     fn apply_after(&mut self, ctx: &mut C) -> Result<Scope, AppError> {
         // Probably net should be unshared in bwrap if user want to use slirp4netns
-        ctx.command_mut().arg("--unshare-net");
+        ctx.args_mut().push(BwrapArg::new("--unshare-net"));
 
-        let scope = self.resolv_conf.mount(ctx.command_mut(), Scope::new());
+        let scope = self.resolv_conf.mount(ctx.args_mut(), Scope::new());
         Ok(scope)
     }
 
+    fn prepare(&mut self) -> Result<(), AppError> {
+        self.resolv_conf.write()
+    }
+
     fn start(mut self: Box<Self>, info: &BwrapInfo) -> Result<HandleType, AppError> {
         self.command
             .arg("--ready-fd")
-            .arg_fd(self.ready.share_tx()?)?
+            .inherit_fd(self.ready.tx())?
+            .arg_fd(self.ready.tx())?
             .arg(info.sandbox.child_pid.to_string())
             .arg(&self.if_name);
 
@@ -75,9 +92,17 @@ impl<C: Context> Service<C> for Slirp4netns {
             .map_err(AppError::spawn(utils::SLIRP4NETNS_CMD))?;
 
         let mut rx = self.ready.into_rx();
-        match rx.try_read_buf_ext::<1>(utils::READY_TIMEOUT) {
+        match rx.try_read_buf_ext::<1>(info.ready_timeout) {
             Ok(_) => Ok(HandleType::new(child)),
             Err(e) => Err(AppError::io("Failed to read slirp4netns ready")(e)),
         }
     }
+
+    fn command(&self) -> Option<&Command> {
+        Some(&self.command)
+    }
+
+    fn command_mut(&mut self) -> Option<&mut Command> {
+        Some(&mut self.command)
+    }
 }
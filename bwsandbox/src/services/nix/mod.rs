@@ -1,23 +1,37 @@
 use crate::services::{BwrapInfo, Context, HandleType, Scope, Service};
-use crate::{error::AppError, utils};
+use crate::{bwrap::BwrapArg, config::TempFileVal, error::AppError, utils};
 use bin::NixBin;
 use serde::Deserialize;
 use std::os::unix::fs::symlink;
 use std::path::PathBuf;
+use std::process::Command;
 
 mod bin;
 mod cmd;
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
+    // Needed for more than one entry, added to service name and overlay dir
+    name: Option<String>,
     #[serde(default)]
     local_read_only: bool,
 }
 
+impl Config {
+    pub fn instance(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+const NAME: &str = "nix-store automapper";
+
 #[derive(Debug)]
 pub struct NixMapper {
+    name: String,
     config: Config,
     tmp_bin: PathBuf,
+    // Store binary linked into overlay dir, created on prepare
+    link: Option<(PathBuf, PathBuf)>,
 }
 
 impl NixMapper {
@@ -25,16 +39,33 @@ impl NixMapper {
     #[allow(clippy::needless_pass_by_value)]
     pub fn from_config(config: Config) -> Result<Self, AppError> {
         let _ = utils::which_bin(utils::NIX_STORE)?;
-        let tmp_bin = utils::temp_dir().join("nix-service-extra-bin-overlay");
-        std::fs::create_dir(&tmp_bin).map_err(AppError::io("failed to create nix overlay"))?;
+        let name = crate::services::instance_name(NAME, config.instance())?;
+        let tmp_bin = TempFileVal::new("nix-service-extra-bin-overlay");
+        let tmp_bin = match config.instance() {
+            Some(instance) => tmp_bin.for_instance(instance),
+            None => tmp_bin,
+        };
 
-        Ok(Self { config, tmp_bin })
+        Ok(Self {
+            name,
+            tmp_bin: tmp_bin.into_inner(),
+            config,
+            link: None,
+        })
+    }
+
+    // Keep interface consistent
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn check_config(config: Config) -> Vec<AppError> {
+        let name = crate::services::instance_name(NAME, config.instance()).err();
+        let bin = utils::which_bin(utils::NIX_STORE).err();
+        name.into_iter().chain(bin).collect()
     }
 }
 
 impl<C: Context> Service<C> for NixMapper {
-    fn name(&self) -> &'static str {
-        "nix-store automapper"
+    fn name(&self) -> &str {
+        &self.name
     }
 
     fn apply_before(&mut self, _: &mut C) -> Result<Scope, AppError> {
@@ -52,28 +83,46 @@ impl<C: Context> Service<C> for NixMapper {
         // Link /nix/store/...<bin> into working dir for future overlay
         // Direct --ro-bind not possible, if binary comes from /usr/bin and it was mounted as ro before
         let bin_name = bin.file_name().expect("Missing sandbox app name?");
-        symlink(bin_nix.readlink(), self.tmp_bin.join(bin_name)).map_err(AppError::io(
-            "Failed to symlink nix store binary into working dir",
-        ))?;
+        self.link = Some((bin_nix.readlink().into(), self.tmp_bin.join(bin_name)));
 
         let parent = bin.parent().expect("Missing sandboxed app parent dir?");
 
         // Mount symlink to real store location of binary to avoid errors of multiple nested symlinks
         // Use overlay to keep all other binaiers in parent dir intact (case for /usr/bin symlink to nix)
-        ctx.command_mut().arg("--overlay-src").arg(parent);
-        ctx.command_mut().arg("--overlay-src").arg(&self.tmp_bin);
-        ctx.command_mut().arg("--ro-overlay").arg(parent);
+        let args = ctx.args_mut();
+        args.push(BwrapArg::new("--overlay-src").arg(parent));
+        args.push(BwrapArg::new("--overlay-src").arg(&self.tmp_bin));
+        args.push(BwrapArg::new("--ro-overlay").arg(parent));
 
         let deps = bin_nix.list_deps(self.config.local_read_only)?;
         for it in deps.iter() {
             let it = it?;
-            ctx.command_mut().arg("--ro-bind").arg(it).arg(it);
+            ctx.args_mut()
+                .push(BwrapArg::new("--ro-bind").arg(it).arg(it));
         }
 
         Ok(Scope::new())
     }
 
+    fn prepare(&mut self) -> Result<(), AppError> {
+        let Some((target, link)) = &self.link else {
+            return Ok(());
+        };
+        std::fs::create_dir(&self.tmp_bin).map_err(AppError::io("failed to create nix overlay"))?;
+        symlink(target, link).map_err(AppError::io(
+            "Failed to symlink nix store binary into working dir",
+        ))
+    }
+
     fn start(self: Box<Self>, _: &BwrapInfo) -> Result<HandleType, AppError> {
         Ok(HandleType::None)
     }
+
+    fn command(&self) -> Option<&Command> {
+        None
+    }
+
+    fn command_mut(&mut self) -> Option<&mut Command> {
+        None
+    }
 }
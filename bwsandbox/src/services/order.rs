@@ -0,0 +1,199 @@
+use super::{BoxedService, BwrapInfo, Context, HandleType, Scope, Service};
+use crate::{config::Order, error::AppError};
+use std::{collections::BTreeSet, process::Command};
+
+// Item with indexes of sorted items it has to wait for
+type Sorted<T> = Vec<(T, Vec<usize>)>;
+// Section to report problem for
+type OrderError = (&'static str, AppError);
+
+// Service or its config as referenced in `after` and `before`
+#[derive(Debug)]
+pub struct Node<T> {
+    pub section: &'static str,
+    pub instance: Option<String>,
+    pub order: Order,
+    pub item: T,
+}
+
+impl<T> Node<T> {
+    // `dbus` matches every dbus instance, `dbus.system` only the named one
+    fn matches(&self, reference: &str) -> bool {
+        match reference.split_once('.') {
+            Some((section, instance)) => {
+                self.section == section && self.instance.as_deref() == Some(instance)
+            }
+            None => self.section == reference,
+        }
+    }
+
+    fn label(&self) -> String {
+        match &self.instance {
+            Some(instance) => format!("{}.{instance}", self.section),
+            None => self.section.into(),
+        }
+    }
+}
+
+// Stable topological sort, unrelated services keep the default order
+pub fn sort<T>(nodes: Vec<Node<T>>) -> Result<Sorted<T>, OrderError> {
+    let mut deps = vec![BTreeSet::new(); nodes.len()];
+    for (index, node) in nodes.iter().enumerate() {
+        let lists = [(&node.order.after, true), (&node.order.before, false)];
+        for (list, after) in lists {
+            for reference in list {
+                let found: Vec<_> = (0..nodes.len())
+                    .filter(|v| *v != index && nodes[*v].matches(reference))
+                    .collect();
+                if found.is_empty() {
+                    let reason = format!("{} refers to unknown {reference:?}", node.label());
+                    return Err((node.section, AppError::ServiceOrder(reason)));
+                }
+                for other in found {
+                    if after {
+                        deps[index].insert(other);
+                    } else {
+                        deps[other].insert(index);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut sorted = Vec::with_capacity(nodes.len());
+    let mut position = vec![None; nodes.len()];
+    while sorted.len() < nodes.len() {
+        let next = (0..nodes.len())
+            .find(|v| position[*v].is_none() && deps[*v].iter().all(|d| position[*d].is_some()));
+        let Some(next) = next else {
+            let left: Vec<_> = (0..nodes.len())
+                .filter(|v| position[*v].is_none())
+                .collect();
+            let labels: Vec<_> = left.iter().map(|v| nodes[*v].label()).collect();
+            let reason = format!("cycle between {}", labels.join(", "));
+            return Err((nodes[left[0]].section, AppError::ServiceOrder(reason)));
+        };
+        position[next] = Some(sorted.len());
+        sorted.push(next);
+    }
+
+    let mut items: Vec<_> = nodes.into_iter().map(|v| Some(v.item)).collect();
+    let sorted = sorted.into_iter().map(|index| {
+        let item = items[index].take().expect("Sorted once");
+        let wait = deps[index].iter().filter_map(|v| position[*v]).collect();
+        (item, wait)
+    });
+    Ok(sorted.collect())
+}
+
+// Service started only after the listed services are started
+pub struct Dependent<C: Context> {
+    service: BoxedService<C>,
+    after: Vec<String>,
+}
+
+impl<C: Context> Dependent<C> {
+    pub fn new(service: BoxedService<C>, after: Vec<String>) -> Self {
+        Self { service, after }
+    }
+}
+
+impl<C: Context> Service<C> for Dependent<C> {
+    fn name(&self) -> &str {
+        self.service.name()
+    }
+
+    fn apply_before(&mut self, ctx: &mut C) -> Result<Scope, AppError> {
+        self.service.apply_before(ctx)
+    }
+
+    fn apply_after(&mut self, ctx: &mut C) -> Result<Scope, AppError> {
+        self.service.apply_after(ctx)
+    }
+
+    fn prepare(&mut self) -> Result<(), AppError> {
+        self.service.prepare()
+    }
+
+    fn start(self: Box<Self>, status: &BwrapInfo) -> Result<HandleType, AppError> {
+        self.service.start(status)
+    }
+
+    fn command(&self) -> Option<&Command> {
+        self.service.command()
+    }
+
+    fn command_mut(&mut self) -> Option<&mut Command> {
+        self.service.command_mut()
+    }
+
+    fn after(&self) -> &[String] {
+        &self.after
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(
+        section: &'static str,
+        instance: Option<&str>,
+        after: &[&str],
+        before: &[&str],
+    ) -> Node<String> {
+        let order = Order {
+            after: after.iter().map(|v| (*v).into()).collect(),
+            before: before.iter().map(|v| (*v).into()).collect(),
+        };
+        let instance = instance.map(String::from);
+        let item = match &instance {
+            Some(v) => format!("{section}.{v}"),
+            None => section.into(),
+        };
+        Node {
+            section,
+            instance,
+            order,
+            item,
+        }
+    }
+
+    #[test]
+    fn test_sort() {
+        let sorted = sort(vec![
+            node("dbus", None, &[], &[]),
+            node("seccomp", None, &[], &["dbus"]),
+            node("pasta", None, &[], &[]),
+            node("process", Some("socks"), &[], &[]),
+            node("process", Some("tunnel"), &["pasta", "process.socks"], &[]),
+        ])
+        .unwrap();
+        let items: Vec<_> = sorted.iter().map(|v| v.0.as_str()).collect();
+        assert_eq!(
+            items,
+            [
+                "seccomp",
+                "dbus",
+                "pasta",
+                "process.socks",
+                "process.tunnel"
+            ]
+        );
+        assert_eq!(sorted[1].1, [0]);
+        assert!(sorted[2].1.is_empty());
+        assert_eq!(sorted[4].1, [2, 3]);
+
+        let cycle = sort(vec![
+            node("dbus", None, &["process"], &[]),
+            node("pasta", None, &[], &[]),
+            node("process", Some("a"), &["dbus"], &[]),
+        ]);
+        let (section, e) = cycle.unwrap_err();
+        assert_eq!(section, "dbus");
+        assert!(e.to_string().contains("dbus, process.a"), "{e}");
+
+        let unknown = sort(vec![node("dbus", None, &["process.a"], &[])]);
+        assert!(unknown.is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::services::net::nsfix;
+use crate::services::{BwrapInfo, Context, HandleType, Scope, Service, ServiceCommand};
+use crate::system::{AsFdArg, PollFile, ReadExt, SharedPipe};
+use crate::{config::Cmd, error::AppError, utils};
+use serde::Deserialize;
+use std::{
+    ffi::OsString,
+    os::{fd::AsFd, fd::AsRawFd, unix::net::UnixStream},
+    path::PathBuf,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+pub const NAME: &str = "process";
+
+const CONNECT_INTERVAL: Duration = Duration::from_millis(50);
+
+// Substituted in args and ready paths on start
+const CHILD_PID: &str = "{child_pid}";
+const NETNS: &str = "{netns}";
+const TEMP_DIR: &str = "{temp_dir}";
+const READY_FD: &str = "{ready_fd}";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum ReadyCheck {
+    // Started is ready
+    #[default]
+    None,
+    FileExists {
+        path: String,
+    },
+    // Helper writes a byte into {ready_fd}, f.e. `--ready-fd={ready_fd}`
+    ReadyFd,
+    UnixSocketConnectable {
+        path: String,
+    },
+    // Seconds
+    Delay {
+        seconds: f64,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    // Used in logs, output log file and exit report
+    pub name: String,
+    pub bin: String,
+    // Enter user namespace which owns sandbox netns, needed to join netns with bwrap --dev
+    #[serde(default)]
+    pub enter_userns: bool,
+    #[serde(default)]
+    pub quiet: bool,
+    #[serde(default)]
+    pub ready: ReadyCheck,
+    // Seconds, [lifecycle] ready_timeout default is used if not set
+    pub ready_timeout: Option<f64>,
+    #[serde(flatten)]
+    pub cmd: Cmd,
+}
+
+impl Config {
+    // Same signature as in other services
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn instance(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct HelperProcess {
+    name: String,
+    // Args are added on start, placeholders need sandbox info
+    command: Command,
+    // Same command with raw args, shown by dry-run
+    preview: Command,
+    args: Vec<OsString>,
+    enter_userns: bool,
+    ready: ReadyCheck,
+    ready_timeout: Option<Duration>,
+}
+
+impl HelperProcess {
+    pub fn from_config(config: Config) -> Result<Self, AppError> {
+        let _ = utils::which_bin(&config.bin)?;
+        let args = config.cmd.collect_args()?;
+        let ready_timeout = config.ready_timeout.map(ready_timeout).transpose()?;
+        if let ReadyCheck::Delay { seconds } = config.ready {
+            delay(seconds)?;
+        }
+
+        let mut command = Command::new(&config.bin);
+        let mut preview = Command::new(&config.bin);
+        preview.args(&args);
+        if config.quiet {
+            command.stdout(Stdio::null());
+            command.stderr(Stdio::null());
+        }
+
+        let name = crate::services::instance_name(NAME, config.instance())?;
+        Ok(Self {
+            name,
+            command,
+            preview,
+            args,
+            enter_userns: config.enter_userns,
+            ready: config.ready,
+            ready_timeout,
+        })
+    }
+
+    // Keep interface consistent
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn check_config(config: Config) -> Vec<AppError> {
+        let bin = utils::which_bin(&config.bin).err();
+        let name = crate::services::instance_name(NAME, config.instance()).err();
+        let args = config.cmd.collect_args().err();
+        let timeout = config.ready_timeout.and_then(|v| ready_timeout(v).err());
+        let delay = match config.ready {
+            ReadyCheck::Delay { seconds } => delay(seconds).err(),
+            _ => None,
+        };
+        bin.into_iter()
+            .chain(name)
+            .chain(args)
+            .chain(timeout)
+            .chain(delay)
+            .collect()
+    }
+
+    fn wait_ready(&self, ready: Option<SharedPipe>, info: &BwrapInfo) -> Result<(), AppError> {
+        let timeout = self.ready_timeout.unwrap_or(info.ready_timeout);
+        match &self.ready {
+            ReadyCheck::None => Ok(()),
+            ReadyCheck::FileExists { path } => {
+                let path = PathBuf::from(substitute(path, info, None));
+                PollFile::watch(&path)?.wait_exists(timeout)
+            }
+            ReadyCheck::ReadyFd => {
+                let mut rx = ready.expect("created for ready_fd").into_rx();
+                rx.try_read_buf_ext::<1>(timeout)
+                    .map(|_| ())
+                    .map_err(AppError::io("Failed to read helper process ready"))
+            }
+            ReadyCheck::UnixSocketConnectable { path } => {
+                let path = PathBuf::from(substitute(path, info, None));
+                let started = Instant::now();
+                loop {
+                    match UnixStream::connect(&path) {
+                        Ok(_) => return Ok(()),
+                        Err(e) if started.elapsed() >= timeout => {
+                            return Err(AppError::file(&path)(e));
+                        }
+                        Err(_) => std::thread::sleep(CONNECT_INTERVAL),
+                    }
+                }
+            }
+            ReadyCheck::Delay { seconds } => {
+                std::thread::sleep(delay(*seconds)?);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn ready_timeout(value: f64) -> Result<Duration, AppError> {
+    Duration::try_from_secs_f64(value)
+        .map_err(|_| AppError::Process(format!("invalid ready_timeout {value}")))
+}
+
+fn delay(value: f64) -> Result<Duration, AppError> {
+    Duration::try_from_secs_f64(value)
+        .map_err(|_| AppError::Process(format!("invalid delay {value}")))
+}
+
+// Non UTF-8 values have no placeholders, passed as is
+fn substitute(value: &str, info: &BwrapInfo, ready_fd: Option<i32>) -> String {
+    let pid = info.sandbox.child_pid;
+    let mut value = value
+        .replace(CHILD_PID, &pid.to_string())
+        .replace(NETNS, &format!("/proc/{pid}/ns/net"))
+        .replace(TEMP_DIR, &utils::temp_dir().to_string_lossy());
+    if let Some(fd) = ready_fd {
+        value = value.replace(READY_FD, &fd.to_string());
+    }
+    value
+}
+
+impl<C: Context> Service<C> for HelperProcess {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply_before(&mut self, _ctx: &mut C) -> Result<Scope, AppError> {
+        Ok(Scope::new())
+    }
+
+    fn apply_after(&mut self, _ctx: &mut C) -> Result<Scope, AppError> {
+        Ok(Scope::new())
+    }
+
+    fn start(mut self: Box<Self>, info: &BwrapInfo) -> Result<HandleType, AppError> {
+        let ready = match self.ready {
+            ReadyCheck::ReadyFd => Some(SharedPipe::new()?),
+            _ => None,
+        };
+        let ready_fd = ready.as_ref().map(|v| v.tx().as_fd().as_raw_fd());
+
+        let args = self.args.iter().map(|v| match v.to_str() {
+            Some(v) => OsString::from(substitute(v, info, ready_fd)),
+            None => v.clone(),
+        });
+        self.command.args(args);
+        if let Some(pipe) = &ready {
+            self.command.inherit_fd(pipe.tx())?;
+        }
+
+        if self.enter_userns {
+            nsfix::pre_exec_enter_ns(&mut self.command, info)?;
+        }
+
+        crate::print_command::print_command(&self.command);
+        let child = self
+            .command
+            .spawn_service()
+            .map_err(AppError::spawn(&self.name))?;
+
+        self.wait_ready(ready, info)?;
+        Ok(HandleType::new(child))
+    }
+
+    fn command(&self) -> Option<&Command> {
+        Some(&self.preview)
+    }
+
+    fn command_mut(&mut self) -> Option<&mut Command> {
+        Some(&mut self.command)
+    }
+}
@@ -3,6 +3,8 @@ use serde::{Deserialize, Deserializer, de::Error};
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
+    // Needed for more than one entry, added to service name
+    pub name: Option<String>,
     pub default_action: Action,
     pub extra_arch: Vec<Arch>,
     pub rules: Vec<Rule>,
@@ -10,6 +12,12 @@ pub struct Config {
     pub optimize: Option<FilterAttrOptimize>,
 }
 
+impl Config {
+    pub fn instance(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Rule {
     pub action: Action,
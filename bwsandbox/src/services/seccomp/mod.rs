@@ -1,23 +1,28 @@
+use crate::bwrap::BwrapArg;
 use crate::error::AppError;
 use crate::services::{BwrapInfo, Context, HandleType, Scope, Service};
-use crate::system::{AsFdArg, AsFdExtra};
 use anyhow::Context as _;
 use std::fs::File;
 use std::io::Seek;
+use std::os::fd::AsRawFd;
+use std::process::Command;
 
 mod config;
 mod ffi;
 
 pub use config::Config;
 
+const NAME: &str = "seccomp filter";
+
 #[derive(Debug)]
-#[repr(transparent)]
 pub struct SeccompService {
+    name: String,
     fd: File,
 }
 
 impl SeccompService {
     pub fn from_config(cfg: Config) -> Result<Self, AppError> {
+        let name = crate::services::instance_name(NAME, cfg.instance())?;
         let mut filter = ffi::FilterCtx::new(cfg.default_action).map_err(AppError::SeccompLib)?;
 
         for arch in cfg.extra_arch {
@@ -49,16 +54,20 @@ impl SeccompService {
             .context("Failed to export compiled seccomp filter")
             .map_err(AppError::SeccompLib)?;
 
-        fd.share_with_children()?;
         fd.rewind().map_err(AppError::file("__seccomp-bpf__"))?;
 
-        Ok(Self { fd })
+        Ok(Self { name, fd })
+    }
+
+    // Compile filter and drop it, libseccomp reports invalid rules only at this point
+    pub fn check_config(cfg: Config) -> Vec<AppError> {
+        Self::from_config(cfg).err().into_iter().collect()
     }
 }
 
 impl<C: Context> Service<C> for SeccompService {
-    fn name(&self) -> &'static str {
-        "seccomp filter"
+    fn name(&self) -> &str {
+        &self.name
     }
 
     fn apply_before(&mut self, _ctx: &mut C) -> Result<Scope, AppError> {
@@ -66,11 +75,28 @@ impl<C: Context> Service<C> for SeccompService {
     }
 
     fn apply_after(&mut self, ctx: &mut C) -> Result<Scope, AppError> {
-        ctx.command_mut().arg("--seccomp").arg_fd(&self.fd)?;
+        // bwrap loads a single --seccomp program
+        if ctx.args().iter().any(|v| v.is("--seccomp")) {
+            let reason = format!("{}: only one seccomp filter is allowed", self.name);
+            return Err(AppError::Instance(reason));
+        }
+        ctx.args_mut()
+            .push(BwrapArg::new("--seccomp").arg_fd(&self.fd));
+        // Stays CLOEXEC for hooks and helpers, `enter` reads it from /proc/<owner>/fd
+        ctx.inherit_fd(self.fd.as_raw_fd());
         Ok(Scope::new())
     }
 
     fn start(self: Box<Self>, _: &BwrapInfo) -> Result<HandleType, AppError> {
-        Ok(HandleType::None)
+        // Kept open for `enter`, which applies the same filter
+        Ok(HandleType::new(self.fd))
+    }
+
+    fn command(&self) -> Option<&Command> {
+        None
+    }
+
+    fn command_mut(&mut self) -> Option<&mut Command> {
+        None
     }
 }
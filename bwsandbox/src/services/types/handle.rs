@@ -1,24 +1,34 @@
-use crate::error::AppError;
+use crate::{error::AppError, system::ResourceUsage};
 
-pub trait Handle: std::fmt::Debug {
+pub trait Handle: std::fmt::Debug + Send {
     fn stop(&mut self) -> Result<(), AppError>;
+
+    // Available after stop for handles which own a process
+    fn usage(&self) -> Option<ResourceUsage> {
+        None
+    }
 }
 
 // New type to force all services to use extra .spawn_service() which wraps Child into HandleType
 // Constructor is private to avoid zombie process if error occured in between spawn() and return HandleType::new(child)
 #[derive(Debug)]
-#[repr(transparent)]
-pub struct ChildHandle(Option<std::process::Child>);
+pub struct ChildHandle {
+    child: Option<std::process::Child>,
+    usage: Option<ResourceUsage>,
+}
 
 impl ChildHandle {
     pub(super) fn new(child: std::process::Child) -> Self {
-        Self(Some(child))
+        Self {
+            child: Some(child),
+            usage: None,
+        }
     }
 }
 
 impl Handle for ChildHandle {
     fn stop(&mut self) -> Result<(), AppError> {
-        let Some(mut child) = std::mem::take(&mut self.0) else {
+        let Some(mut child) = std::mem::take(&mut self.child) else {
             return Ok(());
         };
 
@@ -26,12 +36,17 @@ impl Handle for ChildHandle {
             log::error!("Failed to kill service child: {e:?}");
         }
 
-        if let Err(e) = child.wait() {
-            log::error!("Failed to wait for service child exit: {e:?}");
+        match ResourceUsage::wait(child.id()) {
+            Ok(usage) => self.usage = Some(usage),
+            Err(e) => log::error!("Failed to wait for service child exit: {e:?}"),
         }
 
         Ok(())
     }
+
+    fn usage(&self) -> Option<ResourceUsage> {
+        self.usage
+    }
 }
 
 impl Drop for ChildHandle {
@@ -61,6 +76,10 @@ impl Handle for Box<dyn Handle> {
     fn stop(&mut self) -> Result<(), AppError> {
         self.as_mut().stop()
     }
+
+    fn usage(&self) -> Option<ResourceUsage> {
+        self.as_ref().usage()
+    }
 }
 
 #[derive(Debug)]
@@ -73,6 +92,13 @@ impl HandleOwned {
         let handle = Box::new(handle);
         Self { handle }
     }
+
+    fn stop(&mut self) -> Option<ResourceUsage> {
+        if let Err(e) = self.handle.stop() {
+            log::error!("Failed to stop service with {e:?}");
+        }
+        self.handle.usage()
+    }
 }
 
 impl Drop for HandleOwned {
@@ -86,13 +112,21 @@ impl Drop for HandleOwned {
 #[derive(Debug)]
 pub enum HandleType {
     None,
-    Owned { _drop: HandleOwned },
+    Owned { handle: HandleOwned },
 }
 
 impl HandleType {
     pub fn new<T: Handle + 'static>(handle: T) -> Self {
         Self::Owned {
-            _drop: HandleOwned::new(handle),
+            handle: HandleOwned::new(handle),
+        }
+    }
+
+    // Stopped explicitly to collect usage, drop does nothing after that
+    pub fn stop(&mut self) -> Option<ResourceUsage> {
+        match self {
+            Self::None => None,
+            Self::Owned { handle } => handle.stop(),
         }
     }
 }
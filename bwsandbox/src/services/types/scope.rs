@@ -22,7 +22,10 @@ impl Scope {
 
     fn cleanup(self) {
         for it in self.remove {
-            if let Err(e) = std::fs::remove_file(&it) {
+            // Dry run never creates them
+            if let Err(e) = std::fs::remove_file(&it)
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
                 log::error!("Failed to remove file {}: {e}", it.display());
             }
         }
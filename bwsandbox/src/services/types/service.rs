@@ -1,35 +1,68 @@
 use super::handle::{ChildHandle, HandleType};
 use super::scope::Scope;
-use crate::{bwrap::SandboxStatus, error::AppError};
-use std::ffi::OsStr;
+use crate::{
+    bwrap::{BwrapArgs, SandboxStatus},
+    error::AppError,
+    system::CgroupConfig,
+    utils,
+};
+use std::{ffi::OsStr, os::fd::RawFd, process::Command, time::Duration};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct BwrapInfo {
     pub pid: u32,
     pub sandbox: SandboxStatus,
+    // How long service may wait for its helper to become ready
+    pub ready_timeout: Duration,
 }
 
 impl BwrapInfo {
     pub fn new(pid: u32, sandbox: SandboxStatus) -> Self {
-        Self { pid, sandbox }
+        Self {
+            pid,
+            sandbox,
+            ready_timeout: utils::READY_TIMEOUT,
+        }
+    }
+
+    pub fn with_ready_timeout(mut self, ready_timeout: Duration) -> Self {
+        self.ready_timeout = ready_timeout;
+        self
     }
 }
 
 pub trait Context: std::fmt::Debug {
-    fn command_mut(&mut self) -> &mut std::process::Command;
-    fn arg_exist_before(&self, arg: &str) -> bool;
+    // Profile args with args added by services so far
+    fn args(&self) -> &BwrapArgs;
+    fn args_mut(&mut self) -> &mut BwrapArgs;
     fn bin(&self) -> &OsStr;
+    // Sandbox is moved into cgroup before app is unblocked, every call adds own limits
+    fn set_cgroup(&mut self, cgroup: CgroupConfig);
+    // Only bwrap inherits the fd, it must stay open until bwrap is spawned
+    fn inherit_fd(&mut self, fd: RawFd);
 }
 
-pub trait Service<C: Context> {
-    fn name(&self) -> &'static str;
+pub trait Service<C: Context>: Send {
+    fn name(&self) -> &str;
     fn apply_before(&mut self, ctx: &mut C) -> Result<Scope, AppError>;
     fn apply_after(&mut self, ctx: &mut C) -> Result<Scope, AppError>;
+    // Host side effects, f.e. files and helper connections, skipped on dry-run
+    fn prepare(&mut self) -> Result<(), AppError> {
+        Ok(())
+    }
     fn start(self: Box<Self>, status: &BwrapInfo) -> Result<HandleType, AppError>;
+    // Helper process command as configured before start, used for dry-run output
+    fn command(&self) -> Option<&Command>;
+    // Used to redirect helper output before start
+    fn command_mut(&mut self) -> Option<&mut Command>;
+    // Names of services which must be started first, others start in parallel
+    fn after(&self) -> &[String] {
+        &[]
+    }
 }
 
 impl<C: Context> Service<C> for Box<dyn Service<C>> {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         self.as_ref().name()
     }
 
@@ -41,9 +74,25 @@ impl<C: Context> Service<C> for Box<dyn Service<C>> {
         self.as_mut().apply_after(ctx)
     }
 
+    fn prepare(&mut self) -> Result<(), AppError> {
+        self.as_mut().prepare()
+    }
+
     fn start(self: Box<Self>, status: &BwrapInfo) -> Result<HandleType, AppError> {
         (*self).start(status)
     }
+
+    fn command(&self) -> Option<&Command> {
+        self.as_ref().command()
+    }
+
+    fn command_mut(&mut self) -> Option<&mut Command> {
+        self.as_mut().command_mut()
+    }
+
+    fn after(&self) -> &[String] {
+        self.as_ref().after()
+    }
 }
 
 // Force spawn_service() instead of spawn() to wrap into Handle with .kill()/.wait() in drop
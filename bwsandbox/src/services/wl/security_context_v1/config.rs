@@ -24,6 +24,9 @@ impl AppId {
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
+    // Needed for more than one context, added to service and socket names
+    #[serde(default)]
+    pub name: Option<String>,
     #[serde(default)]
     pub mount: Option<EnvVal<PathBuf>>,
     #[serde(default = "sandbox_engine_default")]
@@ -34,6 +37,12 @@ pub struct Config {
     pub socket: TempFileVal,
 }
 
+impl Config {
+    pub fn instance(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
 fn app_id_default() -> AppId {
     AppId::Bin(BinResolver)
 }
@@ -3,21 +3,45 @@ mod config;
 
 pub use config::Config;
 
+use crate::bwrap::BwrapArg;
 use crate::error::AppError;
 use crate::services::{BwrapInfo, Context, HandleType, Scope, Service};
-use std::{ffi::CString, io::PipeWriter, os::unix::net::UnixListener};
+use std::{ffi::CString, io::PipeWriter, os::unix::net::UnixListener, process::Command};
 use wayrs_client::Connection;
 use wayrs_protocols::security_context_v1::{WpSecurityContextManagerV1, WpSecurityContextV1};
 
+const NAME: &str = "wl_security_context_v1";
+
 pub struct SecurityContextV1 {
+    name: String,
     config: Config,
+    app_id: Option<CString>,
+    listener: Option<Listener>,
+}
+
+// Compositor side of the context, created only for real launch
+struct Listener {
     conn: Connection<()>,
     context: WpSecurityContextV1,
     close_tx: PipeWriter,
 }
 
 impl SecurityContextV1 {
-    pub fn from_config(config: Config) -> Result<Self, AppError> {
+    pub fn from_config(mut config: Config) -> Result<Self, AppError> {
+        let name = crate::services::instance_name(NAME, config.instance())?;
+        if let Some(instance) = &config.name {
+            config.socket = config.socket.for_instance(instance);
+        }
+
+        Ok(Self {
+            name,
+            config,
+            app_id: None,
+            listener: None,
+        })
+    }
+
+    fn listen(&self) -> Result<Listener, AppError> {
         // Connect and fetch supported protocols
         let mut conn = wayrs_client::Connection::<()>::connect()?;
         conn.blocking_roundtrip()
@@ -27,57 +51,85 @@ impl SecurityContextV1 {
         let manager: WpSecurityContextManagerV1 = conn.bind_singleton(1)?;
 
         // Pass "fake" wayland socket and rx pipe end for control
-        let socket = UnixListener::bind(config.socket.as_inner())
+        let socket = UnixListener::bind(self.config.socket.as_inner())
             .map_err(AppError::io("wl bind security socket"))?;
         let (close_rx, close_tx) = std::io::pipe().map_err(AppError::io("wl control pipe"))?;
 
         let context: WpSecurityContextV1 =
             manager.create_listener(&mut conn, socket.into(), close_rx.into());
 
-        Ok(Self {
-            config,
+        Ok(Listener {
             conn,
             context,
             close_tx,
         })
     }
+
+    // Keep interface consistent
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn check_config(config: Config) -> Vec<AppError> {
+        crate::services::instance_name(NAME, config.instance())
+            .err()
+            .into_iter()
+            .collect()
+    }
 }
 
 impl<C: Context> Service<C> for SecurityContextV1 {
-    fn name(&self) -> &'static str {
-        "wl_security_context_v1"
+    fn name(&self) -> &str {
+        &self.name
     }
 
     fn apply_before(&mut self, ctx: &mut C) -> Result<Scope, AppError> {
-        let engine = CString::new(self.config.sandbox_engine.clone())?;
-        self.context.set_sandbox_engine(&mut self.conn, engine);
-
-        let app_id = self.config.app_id.resolve(ctx.bin())?;
-        self.context.set_app_id(&mut self.conn, app_id);
-
+        self.app_id = Some(self.config.app_id.resolve(ctx.bin())?);
         Ok(Scope::new().remove_file(self.config.socket.as_inner()))
     }
 
     fn apply_after(&mut self, ctx: &mut C) -> Result<Scope, AppError> {
         if let Some(mount) = &self.config.mount {
-            ctx.command_mut()
-                .arg("--bind")
+            let arg = BwrapArg::new("--bind")
                 .arg(self.config.socket.as_inner())
                 .arg(mount.as_inner());
+            ctx.args_mut().push(arg);
         }
         Ok(Scope::new())
     }
 
-    fn start(mut self: Box<Self>, _: &BwrapInfo) -> Result<HandleType, AppError> {
-        self.context.commit(&mut self.conn);
-        self.context.destroy(&mut self.conn);
-        self.conn
-            .flush(wayrs_client::IoMode::Blocking)
+    fn prepare(&mut self) -> Result<(), AppError> {
+        let mut listener = self.listen()?;
+        let Listener { conn, context, .. } = &mut listener;
+
+        let engine = CString::new(self.config.sandbox_engine.clone())?;
+        context.set_sandbox_engine(conn, engine);
+        if let Some(app_id) = self.app_id.take() {
+            context.set_app_id(conn, app_id);
+        }
+
+        self.listener = Some(listener);
+        Ok(())
+    }
+
+    fn start(self: Box<Self>, _: &BwrapInfo) -> Result<HandleType, AppError> {
+        let Listener {
+            mut conn,
+            context,
+            close_tx,
+        } = self.listener.expect("wl service started before prepare");
+        context.commit(&mut conn);
+        context.destroy(&mut conn);
+        conn.flush(wayrs_client::IoMode::Blocking)
             .map_err(AppError::io("wl flush"))?;
 
-        self.conn
-            .blocking_roundtrip()
+        conn.blocking_roundtrip()
             .map_err(AppError::io("wl roundtrip after commit"))?;
-        Ok(HandleType::new(self.close_tx))
+        Ok(HandleType::new(close_tx))
+    }
+
+    fn command(&self) -> Option<&Command> {
+        None
+    }
+
+    fn command_mut(&mut self) -> Option<&mut Command> {
+        None
     }
 }
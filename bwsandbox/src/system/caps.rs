@@ -0,0 +1,92 @@
+use super::Error;
+use rustix::thread::{self, CapabilitySet, CapabilitySets};
+
+// Capability sets of a process as shown in /proc/<pid>/status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    sets: CapabilitySets,
+    bounding: CapabilitySet,
+    ambient: CapabilitySet,
+}
+
+impl Capabilities {
+    pub fn of_pid(pid: u32) -> Result<Self, Error> {
+        let path = format!("/proc/{pid}/status");
+        let status = std::fs::read_to_string(path).map_err(Error::Caps)?;
+        Self::parse(&status).ok_or_else(|| Error::Caps(std::io::ErrorKind::InvalidData.into()))
+    }
+
+    fn parse(status: &str) -> Option<Self> {
+        let value = |key: &str| {
+            let line = status.lines().find_map(|v| v.strip_prefix(key))?;
+            let bits = u64::from_str_radix(line.trim(), 16).ok()?;
+            Some(CapabilitySet::from_bits_retain(bits))
+        };
+        Some(Self {
+            sets: CapabilitySets {
+                effective: value("CapEff:")?,
+                permitted: value("CapPrm:")?,
+                inheritable: value("CapInh:")?,
+            },
+            bounding: value("CapBnd:")?,
+            ambient: value("CapAmb:")?,
+        })
+    }
+
+    // Doesn't allocate, safe to call in pre_exec. Caller must be able to drop bounding set,
+    // f.e. has full capabilities after setns into user namespace
+    pub fn apply(&self) -> std::io::Result<()> {
+        // Same as bwrap does for the app, exec can't gain privileges back
+        thread::set_no_new_privs(true)?;
+
+        for bit in 0..u64::BITS {
+            let cap = CapabilitySet::from_bits_retain(1 << bit);
+            // Kernel reports unknown capabilities as invalid, all known are checked by now
+            match thread::capability_is_in_bounding_set(cap) {
+                Ok(true) if !self.bounding.contains(cap) => {
+                    thread::remove_capability_from_bounding_set(cap)?;
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+
+        thread::clear_ambient_capability_set()?;
+        thread::set_capabilities(None, self.sets)?;
+        // Ambient caps need permitted and inheritable ones, so they go last
+        for bit in 0..u64::BITS {
+            let cap = CapabilitySet::from_bits_retain(1 << bit);
+            if self.ambient.contains(cap) {
+                thread::configure_capability_in_ambient_set(cap, true)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::CommandExt;
+
+    #[test]
+    fn test_parse() {
+        let status = "Name:\tsh\nCapInh:\t0000000000000000\nCapPrm:\t0000000000000000\n\
+            CapEff:\t0000000000000000\nCapBnd:\t0000000000000400\nCapAmb:\t0000000000000000\n";
+        let caps = Capabilities::parse(status).unwrap();
+        assert!(caps.sets.effective.is_empty());
+        assert_eq!(caps.bounding, CapabilitySet::NET_BIND_SERVICE);
+
+        assert!(Capabilities::parse("Name:\tsh\n").is_none());
+
+        // Own sets are always allowed
+        let caps = Capabilities::of_pid(std::process::id()).unwrap();
+        let status = unsafe {
+            std::process::Command::new("true")
+                .pre_exec(move || caps.apply())
+                .status()
+                .unwrap()
+        };
+        assert!(status.success());
+    }
+}
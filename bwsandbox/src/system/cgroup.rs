@@ -0,0 +1,237 @@
+use crate::{error::AppError, system::ResourceUsage, utils};
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const REMOVE_TIMEOUT: Duration = Duration::from_secs(1);
+const REMOVE_RETRY: Duration = Duration::from_millis(10);
+
+// systemd delegates user@<uid>.service subtree to the user, so it can be changed without root
+pub fn delegated_root() -> Result<PathBuf, AppError> {
+    let path = Path::new("/proc/self/cgroup");
+    let content = std::fs::read_to_string(path).map_err(AppError::file(path))?;
+    let uid = rustix::process::getuid().as_raw();
+    parse_delegated_root(&content, uid)
+}
+
+fn parse_delegated_root(content: &str, uid: u32) -> Result<PathBuf, AppError> {
+    // cgroup v2 has single "0::/path" entry
+    let current = content
+        .lines()
+        .find_map(|v| v.strip_prefix("0::"))
+        .ok_or_else(|| AppError::Cgroup("cgroup v2 hierarchy is not mounted".into()))?;
+
+    let service = format!("user@{uid}.service");
+    let mut root = PathBuf::from(CGROUP_ROOT);
+    for it in Path::new(current).iter().skip(1) {
+        root.push(it);
+        if it == service.as_str() {
+            return Ok(root);
+        }
+    }
+    Err(AppError::Cgroup(format!(
+        "{service} is not found in current cgroup {current}"
+    )))
+}
+
+// Cgroup of the sandbox with the given id, id comes from user for freeze/thaw
+pub fn sandbox_path(root: &Path, id: &str) -> Result<PathBuf, AppError> {
+    if !utils::is_rand_id(id) {
+        return Err(AppError::Cgroup(format!("invalid sandbox id {id:?}")));
+    }
+    Ok(root.join(format!("{}-{id}", utils::APP_NAME)))
+}
+
+// Frozen processes stay in memory but never get scheduled until thawed
+pub fn set_frozen(path: &Path, frozen: bool) -> Result<(), AppError> {
+    if !path.is_dir() {
+        let reason = format!("sandbox cgroup {} does not exist", path.display());
+        return Err(AppError::Cgroup(reason));
+    }
+    write(path, "cgroup.freeze", if frozen { "1" } else { "0" })
+}
+
+// SIGKILL for the whole tree at once, without pid reuse races
+pub fn kill(path: &Path) -> Result<(), AppError> {
+    write(path, "cgroup.kill", "1")
+}
+
+// Children spawned later inherit the cgroup
+pub fn add_pid(path: &Path, pid: u32) -> Result<(), AppError> {
+    write(path, "cgroup.procs", &pid.to_string())
+}
+
+fn write(path: &Path, file: &str, value: &str) -> Result<(), AppError> {
+    let path = path.join(file);
+    std::fs::write(&path, value).map_err(AppError::file(path))
+}
+
+// Cgroup to create on spawn, nothing touched on the host before that
+#[derive(Debug, Clone)]
+pub struct CgroupConfig {
+    path: PathBuf,
+    // Interface file name with value, f.e. `memory.max`
+    limits: Vec<(&'static str, String)>,
+}
+
+impl CgroupConfig {
+    pub fn new(path: PathBuf, limits: Vec<(&'static str, String)>) -> Self {
+        Self { path, limits }
+    }
+
+    // Same sandbox cgroup, later limits override earlier ones on create
+    pub fn extend(&mut self, other: Self) {
+        self.limits.extend(other.limits);
+    }
+
+    pub fn create(self) -> Result<Cgroup, AppError> {
+        let parent = self.path.parent().expect("Missing cgroup parent?");
+        self.enable_controllers(parent)?;
+
+        std::fs::create_dir(&self.path).map_err(AppError::file(&self.path))?;
+        let cgroup = Cgroup { path: self.path };
+        for (file, value) in &self.limits {
+            write(&cgroup.path, file, value)?;
+        }
+
+        log::info!("Sandbox cgroup created: {}", cgroup.path.display());
+        Ok(cgroup)
+    }
+
+    // Limits are available only if parent passes controllers to children
+    fn enable_controllers(&self, parent: &Path) -> Result<(), AppError> {
+        let subtree = parent.join("cgroup.subtree_control");
+        let enabled = std::fs::read_to_string(&subtree).map_err(AppError::file(&subtree))?;
+        let enabled = enabled.split_whitespace().collect::<Vec<_>>();
+
+        let mut missing = self
+            .limits
+            .iter()
+            .filter_map(|(file, _)| file.split_once('.').map(|v| v.0))
+            .filter(|v| !enabled.contains(v))
+            .map(|v| format!("+{v}"))
+            .collect::<Vec<_>>();
+        missing.dedup();
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+        std::fs::write(&subtree, missing.join(" ")).map_err(AppError::file(&subtree))
+    }
+}
+
+// Removed on drop, every process inside must be gone by then
+#[derive(Debug)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    pub fn add_pid(&self, pid: u32) -> Result<(), AppError> {
+        add_pid(&self.path, pid)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn kill(&self) -> Result<(), AppError> {
+        kill(&self.path)
+    }
+
+    // Counters of the whole tree including escaped processes, rusage used for missing controllers
+    pub fn usage(&self, mut usage: ResourceUsage) -> ResourceUsage {
+        let read = |file| std::fs::read_to_string(self.path.join(file)).ok();
+        if let Some(stat) = read("cpu.stat") {
+            usage.user_ms = stat_value(&stat, "user_usec").map_or(usage.user_ms, |v| v / 1000);
+            usage.system_ms =
+                stat_value(&stat, "system_usec").map_or(usage.system_ms, |v| v / 1000);
+        }
+        if let Some(peak) = read("memory.peak").and_then(|v| v.trim().parse::<u64>().ok()) {
+            usage.peak_rss_kib = peak / 1024;
+        }
+        if let Some(stat) = read("io.stat") {
+            (usage.read_bytes, usage.write_bytes) = io_bytes(&stat);
+        }
+        usage
+    }
+
+    fn remove(&self) -> Result<(), AppError> {
+        // Killed processes leave cgroup asynchronously
+        let started = Instant::now();
+        loop {
+            match std::fs::remove_dir(&self.path) {
+                Err(e)
+                    if e.kind() == ErrorKind::ResourceBusy
+                        && started.elapsed() < REMOVE_TIMEOUT =>
+                {
+                    std::thread::sleep(REMOVE_RETRY);
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+                status => return status.map_err(AppError::file(&self.path)),
+            }
+        }
+    }
+}
+
+// "key value" lines, f.e. cpu.stat
+fn stat_value(stat: &str, key: &str) -> Option<u64> {
+    stat.lines()
+        .find_map(|v| v.strip_prefix(key)?.strip_prefix(' '))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+// "8:0 rbytes=1 wbytes=2 rios=3 ..." line per device
+fn io_bytes(stat: &str) -> (u64, u64) {
+    let mut bytes = (0, 0);
+    for it in stat.split_whitespace().filter_map(|v| v.split_once('=')) {
+        match it {
+            ("rbytes", v) => bytes.0 += v.parse::<u64>().unwrap_or_default(),
+            ("wbytes", v) => bytes.1 += v.parse::<u64>().unwrap_or_default(),
+            _ => {}
+        }
+    }
+    bytes
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        if let Err(e) = self.remove() {
+            log::error!("Failed to remove sandbox cgroup: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delegated_root() {
+        let content = "0::/user.slice/user-1000.slice/user@1000.service/app.slice/term.scope\n";
+        assert_eq!(
+            parse_delegated_root(content, 1000).unwrap(),
+            Path::new("/sys/fs/cgroup/user.slice/user-1000.slice/user@1000.service")
+        );
+        assert!(parse_delegated_root(content, 1001).is_err());
+        assert!(parse_delegated_root("1:memory:/\n", 1000).is_err());
+
+        let root = Path::new("/sys/fs/cgroup");
+        assert!(sandbox_path(root, "aZ0-_").is_ok());
+        assert!(sandbox_path(root, "../x").is_err());
+        assert!(sandbox_path(root, "").is_err());
+    }
+
+    #[test]
+    fn test_stat() {
+        let stat = "usage_usec 3500\nuser_usec 2000\nsystem_usec 1500\n";
+        assert_eq!(stat_value(stat, "user_usec"), Some(2000));
+        assert_eq!(stat_value(stat, "usage"), None);
+
+        let stat = "8:0 rbytes=100 wbytes=20 rios=1 wios=1\n8:16 rbytes=5 wbytes=0 rios=1 wios=0\n";
+        assert_eq!(io_bytes(stat), (105, 20));
+    }
+}
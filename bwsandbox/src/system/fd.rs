@@ -1,7 +1,8 @@
 use crate::{error::AppError, system::poll::Poll};
 use std::{
     io::Read,
-    os::fd::{AsFd, AsRawFd},
+    os::fd::{AsFd, AsRawFd, BorrowedFd},
+    os::unix::process::CommandExt,
     process::Command,
     time::Duration,
 };
@@ -23,6 +24,7 @@ impl<T: AsFd> AsFdExtra for T {
 
 pub trait AsFdArg<T: AsFd> {
     fn arg_fd(&mut self, fd: &T) -> Result<&mut Command, AppError>;
+    fn inherit_fd(&mut self, fd: &T) -> Result<&mut Command, AppError>;
 }
 
 impl<T: AsFd> AsFdArg<T> for std::process::Command {
@@ -30,6 +32,25 @@ impl<T: AsFd> AsFdArg<T> for std::process::Command {
         let command = self.arg(fd.as_fd().as_raw_fd().to_string());
         Ok(command)
     }
+
+    // Services are spawned from parallel threads, fd shared in parent would leak into
+    // every child spawned meanwhile, so CLOEXEC is cleared only in this child
+    fn inherit_fd(&mut self, fd: &T) -> Result<&mut Command, AppError> {
+        use rustix::io::fcntl_setfd;
+
+        let raw = fd.as_fd().as_raw_fd();
+        let pre_exec = move || -> Result<(), std::io::Error> {
+            // Parent keeps fd open until spawn returns
+            let fd = unsafe { BorrowedFd::borrow_raw(raw) };
+            fcntl_setfd(fd, rustix::io::FdFlags::empty()).map_err(std::io::Error::from)
+        };
+
+        unsafe {
+            self.pre_exec(pre_exec);
+        }
+
+        Ok(self)
+    }
 }
 
 pub trait ReadExt {
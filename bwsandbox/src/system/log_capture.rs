@@ -0,0 +1,251 @@
+use super::Error;
+use std::{
+    fs::{File, OpenOptions},
+    io::{PipeWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+// Escaped processes may keep pipe open forever, do not wait for them
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+const BUF_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rotation {
+    // Current file is rotated once it grows over this size in bytes
+    pub max_size: u64,
+    // Rotated files kept as <name>.1 .. <name>.<keep>, oldest removed
+    pub keep: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tee {
+    Off,
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug)]
+struct RotatingLog {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    rotation: Rotation,
+}
+
+impl RotatingLog {
+    fn open(path: PathBuf, rotation: Rotation) -> Result<Self, Error> {
+        let file = Self::open_file(&path)?;
+        let size = file.metadata().map_err(Error::Log)?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            rotation,
+        })
+    }
+
+    fn open_file(path: &Path) -> Result<File, Error> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(Error::Log)
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{index}"));
+        name.into()
+    }
+
+    fn rotate(&mut self) -> Result<(), Error> {
+        if self.rotation.keep == 0 {
+            self.file.set_len(0).map_err(Error::Log)?;
+        } else {
+            for index in (1..self.rotation.keep).rev() {
+                let from = self.rotated(index);
+                if from.exists() {
+                    std::fs::rename(&from, self.rotated(index + 1)).map_err(Error::Log)?;
+                }
+            }
+            std::fs::rename(&self.path, self.rotated(1)).map_err(Error::Log)?;
+            self.file = Self::open_file(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        if self.size > 0 && self.size + data.len() as u64 > self.rotation.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(data).map_err(Error::Log)?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+}
+
+// Output of one process, stdout and stderr share the file
+#[derive(Debug)]
+pub struct LogCapture {
+    log: Arc<Mutex<RotatingLog>>,
+    timestamps: bool,
+    drained: Vec<Receiver<()>>,
+}
+
+impl LogCapture {
+    pub fn open(path: PathBuf, rotation: Rotation, timestamps: bool) -> Result<Self, Error> {
+        let log = RotatingLog::open(path, rotation)?;
+        Ok(Self {
+            log: Arc::new(Mutex::new(log)),
+            timestamps,
+            drained: Vec::new(),
+        })
+    }
+
+    // Write end goes to the process, copy ends once every copy of it is closed
+    pub fn pipe(&mut self, tee: Tee) -> Result<PipeWriter, Error> {
+        let (reader, writer) = std::io::pipe().map_err(Error::Log)?;
+        let (tx, rx) = mpsc::channel();
+        let log = self.log.clone();
+        let timestamps = self.timestamps;
+        std::thread::spawn(move || {
+            copy(reader, &log, timestamps, tee);
+            let _ = tx.send(());
+        });
+        self.drained.push(rx);
+        Ok(writer)
+    }
+}
+
+impl Drop for LogCapture {
+    fn drop(&mut self) {
+        let deadline = Instant::now() + DRAIN_TIMEOUT;
+        for it in &self.drained {
+            let _ = it.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+        }
+    }
+}
+
+fn copy(mut reader: impl Read, log: &Mutex<RotatingLog>, timestamps: bool, tee: Tee) {
+    let mut buf = [0; BUF_SIZE];
+    let mut line_start = true;
+    let mut log_failed = false;
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(v) => v,
+        };
+        let data = &buf[..read];
+
+        // Terminal copy is best effort, log keeps everything
+        let _ = match tee {
+            Tee::Off => Ok(()),
+            Tee::Stdout => std::io::stdout().write_all(data),
+            Tee::Stderr => std::io::stderr().write_all(data),
+        };
+
+        let data = if timestamps {
+            with_timestamps(data, &mut line_start)
+        } else {
+            data.to_vec()
+        };
+        let status = log.lock().map_err(|_| ()).and_then(|mut v| {
+            v.write(&data).map_err(|e| {
+                if !log_failed {
+                    log::error!("Failed to write output log: {e}");
+                }
+            })
+        });
+        log_failed = status.is_err();
+    }
+}
+
+fn with_timestamps(data: &[u8], line_start: &mut bool) -> Vec<u8> {
+    let stamp = timestamp(SystemTime::now());
+    let mut out = Vec::with_capacity(data.len() + stamp.len());
+    for line in data.split_inclusive(|v| *v == b'\n') {
+        if *line_start {
+            out.extend_from_slice(stamp.as_bytes());
+        }
+        out.extend_from_slice(line);
+        *line_start = line.ends_with(b"\n");
+    }
+    out
+}
+
+// RFC 3339 in UTC, f.e. `2024-05-01T10:20:30.123Z `
+fn timestamp(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    let (days, day_secs) = (secs / 86400, secs % 86400);
+
+    // Days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days.cast_signed() + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z ",
+        day_secs / 3600,
+        day_secs / 60 % 60,
+        day_secs % 60,
+        since.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_714_558_830_123);
+        assert_eq!(timestamp(time), "2024-05-01T10:20:30.123Z ");
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z ");
+
+        let mut line_start = true;
+        let out = with_timestamps(b"a\nb", &mut line_start);
+        assert_eq!(String::from_utf8(out).unwrap().matches("Z ").count(), 2);
+        assert!(!line_start);
+        let out = with_timestamps(b"c\n", &mut line_start);
+        assert_eq!(out, b"c\n");
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let rotation = Rotation {
+            max_size: 4,
+            keep: 2,
+        };
+        let mut capture = LogCapture::open(path.clone(), rotation, false).unwrap();
+        let mut writer = capture.pipe(Tee::Off).unwrap();
+        writer.write_all(b"aaa").unwrap();
+        drop(writer);
+        drop(capture);
+
+        // Every launch appends, rotation happens on write
+        for data in [b"bbb", b"ccc", b"ddd"] {
+            let mut capture = LogCapture::open(path.clone(), rotation, false).unwrap();
+            capture.pipe(Tee::Off).unwrap().write_all(data).unwrap();
+        }
+        let read = |v: &Path| std::fs::read_to_string(v).unwrap();
+        assert_eq!(read(&path), "ddd");
+        assert_eq!(read(&dir.path().join("app.log.1")), "ccc");
+        assert_eq!(read(&dir.path().join("app.log.2")), "bbb");
+        assert!(!dir.path().join("app.log.3").exists());
+    }
+}
@@ -1,14 +1,26 @@
+mod caps;
+pub mod cgroup;
 mod fd;
+mod log_capture;
 mod namespaces;
 mod pidfd;
 mod poll;
 mod poll_file;
+mod pty;
+mod rusage;
+mod seccomp;
 mod shared_pipe;
 
+pub use caps::Capabilities;
+pub use cgroup::{Cgroup, CgroupConfig};
 pub use fd::{AsFdArg, AsFdExtra, ReadExt};
+pub use log_capture::{LogCapture, Rotation, Tee};
 pub use namespaces::{Namespace, NamespaceType};
 pub use pidfd::PidFd;
 pub use poll_file::PollFile;
+pub use pty::{Pty, PtyRelay};
+pub use rusage::ResourceUsage;
+pub use seccomp::SeccompFilter;
 pub use shared_pipe::SharedPipe;
 
 #[allow(clippy::enum_variant_names)]
@@ -32,4 +44,14 @@ pub enum Error {
     InotWatch(rustix::io::Errno),
     #[error(transparent)]
     InotRead(rustix::io::Errno),
+    #[error(transparent)]
+    Wait(std::io::Error),
+    #[error("Pseudoterminal: {0}")]
+    Pty(std::io::Error),
+    #[error("Output log: {0}")]
+    Log(std::io::Error),
+    #[error("Capabilities: {0}")]
+    Caps(std::io::Error),
+    #[error("Invalid compiled seccomp filter size {0}")]
+    SeccompBpf(usize),
 }
@@ -11,14 +11,22 @@ mod ioctl;
 #[derive(Debug, Clone, Copy)]
 pub enum NamespaceType {
     User,
+    Mnt,
+    Pid,
     Net,
+    Ipc,
+    Uts,
 }
 
 impl AsRef<str> for NamespaceType {
     fn as_ref(&self) -> &str {
         match self {
             Self::User => "user",
+            Self::Mnt => "mnt",
+            Self::Pid => "pid",
             Self::Net => "net",
+            Self::Ipc => "ipc",
+            Self::Uts => "uts",
         }
     }
 }
@@ -27,7 +35,11 @@ impl NamespaceType {
     fn link_type(self) -> LinkNameSpaceType {
         match self {
             Self::User => LinkNameSpaceType::User,
+            Self::Mnt => LinkNameSpaceType::Mount,
+            Self::Pid => LinkNameSpaceType::ProcessID,
             Self::Net => LinkNameSpaceType::Network,
+            Self::Ipc => LinkNameSpaceType::InterProcessCommunication,
+            Self::Uts => LinkNameSpaceType::HostNameAndNISDomainName,
         }
     }
 }
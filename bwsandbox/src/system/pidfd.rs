@@ -18,6 +18,11 @@ impl PidFd {
         Ok(Self { fd })
     }
 
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        let fd = rustix::io::fcntl_dupfd_cloexec(&self.fd, 0).map_err(Error::PidFdOpen)?;
+        Ok(Self { fd })
+    }
+
     pub fn send_sig(&self, sig: Signal) -> Result<(), Error> {
         rustix::process::pidfd_send_signal(&self.fd, sig).map_err(Error::PidFdSig)?;
         Ok(())
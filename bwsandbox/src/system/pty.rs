@@ -0,0 +1,216 @@
+use super::{Error, poll::Poll};
+use rustix::pty::OpenptFlags;
+use rustix::termios::{self, OptionalActions, Termios};
+use signal_hook::{consts::SIGWINCH, iterator::Signals};
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    os::{fd::OwnedFd, unix::process::CommandExt},
+    process::{Command, Stdio},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering::SeqCst},
+        mpsc::{self, Receiver},
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+const INPUT_POLL: Duration = Duration::from_millis(100);
+// Escaped processes may keep slave open forever, do not wait for them
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+const BUF_SIZE: usize = 4096;
+
+// New pseudoterminal, sandbox never touches the user terminal, so TIOCSTI injects only here
+#[derive(Debug)]
+pub struct Pty {
+    master: OwnedFd,
+    slave: OwnedFd,
+}
+
+impl Pty {
+    pub fn open() -> Result<Self, Error> {
+        let flags = OpenptFlags::RDWR | OpenptFlags::NOCTTY | OpenptFlags::CLOEXEC;
+        let master = rustix::pty::openpt(flags).map_err(|e| Error::Pty(e.into()))?;
+        rustix::pty::grantpt(&master).map_err(|e| Error::Pty(e.into()))?;
+        rustix::pty::unlockpt(&master).map_err(|e| Error::Pty(e.into()))?;
+        let slave =
+            rustix::pty::ioctl_tiocgptpeer(&master, flags).map_err(|e| Error::Pty(e.into()))?;
+
+        if let Ok(size) = termios::tcgetwinsize(std::io::stdin()) {
+            let _ = termios::tcsetwinsize(&master, size);
+        }
+        Ok(Self { master, slave })
+    }
+
+    // Slave becomes stdio and controlling terminal of the new session
+    pub fn attach(&self, command: &mut Command) -> Result<(), Error> {
+        let stdio = || self.slave.try_clone().map_err(Error::Pty);
+        command.stdin(Stdio::from(stdio()?));
+        command.stdout(Stdio::from(stdio()?));
+        command.stderr(Stdio::from(stdio()?));
+
+        let slave = stdio()?;
+        unsafe {
+            command.pre_exec(move || {
+                rustix::process::setsid()?;
+                rustix::process::ioctl_tiocsctty(&slave)?;
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    // Command must be dropped before, master gets EIO once every slave copy is closed
+    pub fn relay(self) -> Result<PtyRelay, Error> {
+        drop(self.slave);
+        let master = File::from(self.master);
+        let stdin = std::io::stdin();
+        let terminal = termios::isatty(&stdin);
+
+        // Keys like ctrl+c are handled by the sandbox line discipline, not by the user terminal
+        let restore = if terminal {
+            let orig = termios::tcgetattr(&stdin).map_err(|e| Error::Pty(e.into()))?;
+            let mut raw = orig.clone();
+            raw.make_raw();
+            termios::tcsetattr(&stdin, OptionalActions::Flush, &raw)
+                .map_err(|e| Error::Pty(e.into()))?;
+            Some(orig)
+        } else {
+            None
+        };
+
+        let mut relay = PtyRelay {
+            stop: Arc::new(AtomicBool::new(false)),
+            input: None,
+            drained: None,
+            winch: None,
+            restore,
+        };
+        relay.input = Some(relay_input(clone(&master)?, relay.stop.clone()));
+        relay.drained = Some(relay_output(clone(&master)?));
+        if terminal {
+            relay.winch = Some(relay_winsize(master)?);
+        }
+        Ok(relay)
+    }
+}
+
+fn clone(file: &File) -> Result<File, Error> {
+    file.try_clone().map_err(Error::Pty)
+}
+
+// Polled to stop without waiting for the next key press
+fn relay_input(mut master: File, stop: Arc<AtomicBool>) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let stdin = std::io::stdin();
+        let mut buf = [0; BUF_SIZE];
+        while !stop.load(SeqCst) {
+            match Poll::new(&stdin).poll_in(INPUT_POLL) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::TimedOut => continue,
+                Err(_) => break,
+            }
+            // Unbuffered, std stdin buffer would hide data from poll
+            let read = match rustix::io::read(&stdin, &mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(v) => v,
+            };
+            if master.write_all(&buf[..read]).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+fn relay_output(mut master: File) -> Receiver<()> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut stdout = std::io::stdout();
+        let mut buf = [0; BUF_SIZE];
+        loop {
+            let read = match master.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(v) => v,
+            };
+            if stdout
+                .write_all(&buf[..read])
+                .and_then(|()| stdout.flush())
+                .is_err()
+            {
+                break;
+            }
+        }
+        let _ = tx.send(());
+    });
+    rx
+}
+
+// Size change on master sends SIGWINCH to the sandbox foreground process group
+fn relay_winsize(master: File) -> Result<(signal_hook::iterator::Handle, JoinHandle<()>), Error> {
+    let mut signals = Signals::new([SIGWINCH]).map_err(Error::Pty)?;
+    let handle = signals.handle();
+    let thread = std::thread::spawn(move || {
+        for _ in signals.forever() {
+            if let Ok(size) = termios::tcgetwinsize(std::io::stdin()) {
+                let _ = termios::tcsetwinsize(&master, size);
+            }
+        }
+    });
+    Ok((handle, thread))
+}
+
+// User terminal mode is restored on drop
+#[derive(Debug)]
+pub struct PtyRelay {
+    stop: Arc<AtomicBool>,
+    input: Option<JoinHandle<()>>,
+    drained: Option<Receiver<()>>,
+    winch: Option<(signal_hook::iterator::Handle, JoinHandle<()>)>,
+    restore: Option<Termios>,
+}
+
+impl Drop for PtyRelay {
+    fn drop(&mut self) {
+        self.stop.store(true, SeqCst);
+        if let Some(input) = self.input.take() {
+            let _ = input.join();
+        }
+        if let Some(drained) = self.drained.take() {
+            let _ = drained.recv_timeout(DRAIN_TIMEOUT);
+        }
+        if let Some((handle, thread)) = self.winch.take() {
+            handle.close();
+            let _ = thread.join();
+        }
+        if let Some(orig) = &self.restore {
+            let status = termios::tcsetattr(std::io::stdin(), OptionalActions::Drain, orig);
+            if let Err(e) = status {
+                log::error!("Failed to restore terminal mode: {e:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attach() {
+        let pty = Pty::open().unwrap();
+        let mut command = Command::new("sh");
+        command.args(["-c", "test -t 0 && test -t 1 && tty"]);
+        pty.attach(&mut command).unwrap();
+        let mut child = command.spawn().unwrap();
+        drop(command);
+        drop(pty.slave);
+
+        let mut master = File::from(pty.master);
+        let mut output = Vec::new();
+        // EIO once child exits and closes the last slave copy
+        let _ = master.read_to_end(&mut output);
+        assert!(child.wait().unwrap().success());
+        assert!(String::from_utf8_lossy(&output).starts_with("/dev/pts/"));
+    }
+}
@@ -0,0 +1,89 @@
+use super::Error;
+use linux_raw_sys::general::{__kernel_old_timeval, rusage};
+use serde::Serialize;
+use std::mem::MaybeUninit;
+
+// rusage block I/O counters are in 512 bytes units
+const BLOCK_SIZE: u64 = 512;
+
+// rustix waitpid has no rusage, wait4 comes from the libc std already links
+unsafe extern "C" {
+    // Same prototype as in <sys/wait.h>, rusage layout is the kernel one glibc and musl share
+    fn wait4(pid: i32, status: *mut i32, options: i32, usage: *mut rusage) -> i32;
+}
+
+// Resources used by process and all its waited children
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct ResourceUsage {
+    pub user_ms: u64,
+    pub system_ms: u64,
+    pub peak_rss_kib: u64,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+}
+
+impl ResourceUsage {
+    // Reap child with usage, std Child must not be waited after that
+    pub fn wait(pid: u32) -> Result<Self, Error> {
+        let pid = pid.cast_signed();
+        let mut status = 0;
+        let mut usage = MaybeUninit::<rusage>::zeroed();
+        loop {
+            // SAFETY: both pointers are valid for writes for the whole call, usage is zeroed
+            let ec = unsafe { wait4(pid, &raw mut status, 0, usage.as_mut_ptr()) };
+            if ec != -1 {
+                break;
+            }
+            let e = std::io::Error::last_os_error();
+            if e.kind() != std::io::ErrorKind::Interrupted {
+                return Err(Error::Wait(e));
+            }
+        }
+
+        // SAFETY: zeroed rusage is valid, successful wait4 filled it
+        let usage = unsafe { usage.assume_init() };
+        let counter = |v| u64::try_from(v).unwrap_or_default();
+        Ok(Self {
+            user_ms: millis(usage.ru_utime),
+            system_ms: millis(usage.ru_stime),
+            peak_rss_kib: counter(usage.ru_maxrss),
+            read_bytes: counter(usage.ru_inblock) * BLOCK_SIZE,
+            write_bytes: counter(usage.ru_oublock) * BLOCK_SIZE,
+        })
+    }
+}
+
+impl std::fmt::Display for ResourceUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cpu user {}ms, cpu system {}ms, peak rss {}KiB, read {}B, write {}B",
+            self.user_ms, self.system_ms, self.peak_rss_kib, self.read_bytes, self.write_bytes
+        )
+    }
+}
+
+fn millis(time: __kernel_old_timeval) -> u64 {
+    let ms = time.tv_sec * 1000 + time.tv_usec / 1000;
+    u64::try_from(ms).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Reaped by wait4, not by std
+    #[allow(clippy::zombie_processes)]
+    fn test_wait() {
+        let child = std::process::Command::new("sh")
+            .args(["-c", "i=0; while [ $i -lt 10000 ]; do i=$((i+1)); done"])
+            .spawn()
+            .unwrap();
+        let usage = ResourceUsage::wait(child.id()).unwrap();
+        assert!(usage.peak_rss_kib > 0);
+
+        // Already reaped
+        assert!(ResourceUsage::wait(child.id()).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use super::Error;
+use linux_raw_sys::{
+    prctl::PR_SET_SECCOMP,
+    ptrace::{SECCOMP_MODE_FILTER, sock_filter, sock_fprog},
+};
+use std::ffi::{c_int, c_ulong};
+
+const INSN_SIZE: usize = size_of::<sock_filter>();
+
+// rustix has no PR_SET_SECCOMP wrapper, libc prctl is variadic
+unsafe extern "C" {
+    fn prctl(option: c_int, ...) -> c_int;
+}
+
+// BPF program exported by libseccomp, the same bytes bwrap gets with --seccomp
+#[derive(Debug)]
+pub struct SeccompFilter {
+    insns: Vec<sock_filter>,
+}
+
+impl SeccompFilter {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.is_empty() || !bytes.len().is_multiple_of(INSN_SIZE) {
+            return Err(Error::SeccompBpf(bytes.len()));
+        }
+        let insns = bytes
+            .chunks_exact(INSN_SIZE)
+            .map(|v| sock_filter {
+                code: u16::from_ne_bytes([v[0], v[1]]),
+                jt: v[2],
+                jf: v[3],
+                k: u32::from_ne_bytes([v[4], v[5], v[6], v[7]]),
+            })
+            .collect::<Vec<_>>();
+        if u16::try_from(insns.len()).is_err() {
+            return Err(Error::SeccompBpf(bytes.len()));
+        }
+        Ok(Self { insns })
+    }
+
+    // Doesn't allocate, safe to call in pre_exec
+    pub fn load(&self) -> std::io::Result<()> {
+        // Unprivileged filter requires no_new_privs, bwrap does the same
+        rustix::thread::set_no_new_privs(true)?;
+
+        let prog = sock_fprog {
+            len: u16::try_from(self.insns.len()).unwrap_or(u16::MAX),
+            filter: self.insns.as_ptr().cast_mut(),
+        };
+        let option = PR_SET_SECCOMP.cast_signed();
+        let mode = c_ulong::from(SECCOMP_MODE_FILTER);
+        // SAFETY: prog points to insns, both outlive the call, kernel copies the program
+        let ec = unsafe { prctl(option, mode, &raw const prog) };
+        if ec == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::CommandExt;
+
+    #[test]
+    fn test_from_bytes() {
+        // BPF_RET | BPF_K, SECCOMP_RET_ALLOW
+        let allow = [0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x7f];
+        let filter = SeccompFilter::from_bytes(&allow).unwrap();
+        assert_eq!(filter.insns.len(), 1);
+        assert_eq!(filter.insns[0].code, 0x06);
+        assert_eq!(filter.insns[0].k, 0x7fff0000);
+
+        assert!(SeccompFilter::from_bytes(&[]).is_err());
+        assert!(SeccompFilter::from_bytes(&allow[..7]).is_err());
+
+        let status = unsafe {
+            std::process::Command::new("true")
+                .pre_exec(move || filter.load())
+                .status()
+                .unwrap()
+        };
+        assert!(status.success());
+    }
+}
@@ -88,6 +88,11 @@ impl SharedPipe {
         self.tx.share()
     }
 
+    // Stays CLOEXEC, passed to a single child with `inherit_fd`
+    pub fn tx(&self) -> &PipePart<PipeWriter> {
+        &self.tx
+    }
+
     pub fn into_rx(self) -> PipeReader {
         self.rx.take_part()
     }
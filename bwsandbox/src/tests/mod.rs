@@ -1,7 +1,10 @@
 use std::io::Write;
 
 use crate::{
-    tests::utils::{OutputExtra, cargo_command, cargo_spawn_out},
+    tests::utils::{
+        OutputExtra, cargo_command, cargo_spawn_out, instance_command, runtime_dir, spawn_instance,
+        wait_instance,
+    },
     utils::rand_id,
 };
 
@@ -96,29 +99,34 @@ fn test_env_mapper() {
 #[test]
 fn test_dbus() {
     // TODO: Add some universal tests for blocked services
-    // Only --talk=org.freedesktop.DBus allowed
-    let args = vec![
-        "-f",
+    // Only --talk=org.freedesktop.DBus allowed, as raw args and as typed policy
+    for profile in [
         "./profiles/with-dbus.toml",
-        "--",
-        "dbus-send",
-        "--type=method_call",
-        "--print-reply",
-        "--dest=org.freedesktop.DBus",
-        "/",
-        "org.freedesktop.DBus.ListNames",
-    ];
-    let output = utils::cargo_spawn_out(args).unwrap();
-    let names = output
-        .stdout_str()
-        .lines()
-        .filter(|line| line.contains("string \""))
-        .filter_map(|line| line.split('"').nth(1))
-        // Skip empty services and/or connections
-        .filter(|name| !name.is_empty() && !name.starts_with(':'))
-        .collect::<Vec<_>>();
-    assert!(output.status.success());
-    assert_eq!(names.len(), 1);
+        "./profiles/with-dbus-policy.toml",
+    ] {
+        let args = vec![
+            "-f",
+            profile,
+            "--",
+            "dbus-send",
+            "--type=method_call",
+            "--print-reply",
+            "--dest=org.freedesktop.DBus",
+            "/",
+            "org.freedesktop.DBus.ListNames",
+        ];
+        let output = utils::cargo_spawn_out(args).unwrap();
+        let names = output
+            .stdout_str()
+            .lines()
+            .filter(|line| line.contains("string \""))
+            .filter_map(|line| line.split('"').nth(1))
+            // Skip empty services and/or connections
+            .filter(|name| !name.is_empty() && !name.starts_with(':'))
+            .collect::<Vec<_>>();
+        assert!(output.status.success());
+        assert_eq!(names.len(), 1);
+    }
 }
 
 #[test]
@@ -206,3 +214,683 @@ fn test_app_image() {
     let output = cargo_spawn_out(args).unwrap();
     assert_eq!(output.stdout_str(), "1\n");
 }
+
+#[test]
+fn test_dry_run() {
+    let args = vec![
+        "--dry-run=json",
+        "-f",
+        "./profiles/bwrap-home.toml",
+        "--",
+        "ls",
+        "/",
+    ];
+    let output = cargo_spawn_out(args).unwrap();
+    assert!(output.status.success());
+
+    let dump: serde_json::Value = serde_json::from_str(output.stdout_str()).unwrap();
+    let bwrap_args = dump["bwrap"]["args"].as_array().unwrap();
+    let bwrap_args = bwrap_args
+        .iter()
+        .filter_map(|v| v.as_str())
+        .collect::<Vec<_>>();
+    assert!(bwrap_args.windows(2).any(|v| v == ["--tmpfs", "/home"]));
+    assert_eq!(bwrap_args[bwrap_args.len() - 2..], ["ls", "/"]);
+    assert!(dump["services"].as_array().unwrap().is_empty());
+
+    let args = vec!["--dry-run", "-f", "./profiles/bwrap-home.toml", "--", "ls"];
+    let output = cargo_spawn_out(args).unwrap();
+    assert!(output.stdout_str().starts_with("# bwrap\nbwrap "));
+}
+
+#[test]
+fn test_check() {
+    let args = vec!["check", "-f", "./profiles/check-broken.toml"];
+    let output = cargo_spawn_out(args).unwrap();
+    assert!(!output.status.success());
+
+    let stdout = output.stdout_str();
+    for section in [
+        "[bwrap]",
+        "[seccomp]",
+        "[env_mapper]",
+        "[cgroup]",
+        "[single_instance]",
+        "[lifecycle]",
+        "[output]",
+        "[hooks]",
+        "[process]",
+        "[dbus]",
+        "[unknown_service]",
+    ] {
+        assert!(stdout.contains(section), "Missing {section} problem");
+    }
+    assert!(stdout.contains("relative/dest"), "Missing mount problem");
+    assert!(
+        stdout.contains("used twice"),
+        "Missing instance name problem"
+    );
+    assert!(stdout.contains("[wl_security_context_v1]"));
+
+    let args = vec!["check", "-f", "./profiles/with-seccomp.toml"];
+    let output = cargo_spawn_out(args).unwrap();
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_service_instances() {
+    let output = cargo_command()
+        .args(["--dry-run=json", "-f", "./profiles/with-instances.toml"])
+        .args(["--", "ls"])
+        .env("BWRAP_TEST", "instance")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let dump: serde_json::Value = serde_json::from_str(output.stdout_str()).unwrap();
+    let bwrap_args = dump["bwrap"]["args"].as_array().unwrap();
+    let bwrap_args = bwrap_args
+        .iter()
+        .filter_map(|v| v.as_str())
+        .collect::<Vec<_>>();
+    assert!(bwrap_args.contains(&"--clearenv"));
+    let keep = ["--setenv", "BWRAP_TEST", "instance"];
+    assert!(bwrap_args.windows(keep.len()).any(|v| v == keep));
+    assert!(bwrap_args.contains(&"APPIMAGE_EXTRACT_AND_RUN"));
+
+    let output = cargo_command()
+        .args(["check", "-f", "./profiles/with-instances.toml"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Instance name is validated as for dbus and process
+    let output = cargo_spawn_out(vec![
+        "--dry-run",
+        "-f",
+        "./profiles/with-instances.toml",
+        "--set-service",
+        "env_mapper.keep.name=",
+        "--",
+        "ls",
+    ])
+    .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_freeze() {
+    // Either no delegated cgroup or no such sandbox, never a success
+    let output = cargo_spawn_out(vec!["thaw", "no-such-sandbox"]).unwrap();
+    assert!(!output.status.success());
+
+    let output = cargo_spawn_out(vec!["freeze", "../escape"]).unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_exit_code() {
+    let report = std::env::temp_dir().join(format!("exit-report-{}.json", rand_id(8)));
+    let args = vec![
+        "--exit-report".as_ref(),
+        report.as_os_str(),
+        "-f".as_ref(),
+        "./profiles/bwrap-no-home.toml".as_ref(),
+        "--".as_ref(),
+        "sh".as_ref(),
+        "-c".as_ref(),
+        "exit 3".as_ref(),
+    ];
+    let output = cargo_spawn_out::<&std::ffi::OsStr>(args).unwrap();
+    assert_eq!(output.status.code(), Some(3));
+
+    let content = std::fs::read_to_string(&report).unwrap();
+    std::fs::remove_file(&report).unwrap();
+    let report: serde_json::Value = serde_json::from_str(&content).unwrap();
+    assert_eq!(report["exit_code"], 3);
+    assert!(report["signal"].is_null());
+    assert!(report["child_pid"].as_u64().is_some());
+    assert!(
+        report["usage"]["sandbox"]["peak_rss_kib"]
+            .as_u64()
+            .is_some()
+    );
+    assert!(report["usage"]["services"].as_array().unwrap().is_empty());
+
+    // Killed by signal => 128 + SIGKILL
+    let args = vec![
+        "-f",
+        "./profiles/bwrap-no-home.toml",
+        "--",
+        "sh",
+        "-c",
+        "kill -9 $$",
+    ];
+    let output = cargo_spawn_out(args).unwrap();
+    assert_eq!(output.status.code(), Some(137));
+}
+
+#[test]
+fn test_instances() {
+    let runtime_dir = runtime_dir("instances");
+    let command = |args: &[&str]| instance_command(&runtime_dir, args);
+
+    let args = ["-f", "./profiles/bwrap-no-home.toml", "--", "sleep", "60"];
+    let mut sandbox = spawn_instance(&runtime_dir, &args);
+    let line = wait_instance(&runtime_dir);
+    let columns = line.split('\t').collect::<Vec<_>>();
+    assert_eq!(columns[1], "bwrap-no-home");
+    assert_eq!(columns[4], "sleep 60");
+
+    let output = command(&["enter", columns[0], "--", "sh", "-c", "exit 3"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+
+    let output = command(&["stop", columns[0]]).output().unwrap();
+    assert!(output.status.success());
+    // SIGTERM => 128 + 15
+    assert_eq!(sandbox.wait().unwrap().code(), Some(143));
+
+    let output = command(&["ps"]).output().unwrap();
+    assert!(output.stdout.is_empty());
+    let output = command(&["stop", columns[0]]).output().unwrap();
+    assert!(output.stderr_str().contains("is not running"));
+    let output = command(&["enter", columns[0], "true"]).output().unwrap();
+    assert!(output.stderr_str().contains("is not running"));
+
+    std::fs::remove_dir_all(runtime_dir).unwrap();
+}
+
+#[test]
+fn test_single_instance() {
+    let runtime_dir = runtime_dir("single");
+    let command = |args: &[&str]| instance_command(&runtime_dir, args);
+    let profile = "./profiles/single-instance.toml";
+
+    let mut sandbox = spawn_instance(&runtime_dir, &["-f", profile, "--", "sleep", "60"]);
+    let line = wait_instance(&runtime_dir);
+    let id = line.split('\t').next().unwrap();
+
+    // Runs inside the first sandbox with stdio of the second launch
+    let args = ["-f", profile, "--", "sh", "-c", "echo forwarded; exit 4"];
+    let output = command(&args).output().unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(output.stdout_str().trim(), "forwarded");
+    let output = command(&["ps"]).output().unwrap();
+    assert_eq!(output.stdout_str().lines().count(), 1);
+
+    // Signals of the second launch reach the forwarded command
+    let started = runtime_dir.join("started");
+    let script = format!(
+        "trap 'exit 5' USR1; touch {}; while :; do sleep 0.1; done",
+        started.display()
+    );
+    let mut forwarded = spawn_instance(&runtime_dir, &["-f", profile, "--", "sh", "-c", &script]);
+    let waited = std::time::Instant::now();
+    while !started.exists() {
+        assert!(waited.elapsed().as_secs() < 30, "Command is not forwarded");
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    let pid = rustix::process::Pid::from_child(&forwarded);
+    rustix::process::kill_process(pid, rustix::process::Signal::USR1).unwrap();
+    assert_eq!(forwarded.wait().unwrap().code(), Some(5));
+
+    let output = command(&["stop", id]).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(sandbox.wait().unwrap().code(), Some(143));
+
+    std::fs::remove_dir_all(runtime_dir).unwrap();
+}
+
+#[test]
+fn test_signals() {
+    let runtime_dir = runtime_dir("signals");
+    let profile = "./profiles/with-lifecycle.toml";
+
+    // Forwarded to sandboxed app
+    let script = "trap 'exit 7' USR1; while :; do sleep 0.1; done";
+    let mut sandbox = spawn_instance(&runtime_dir, &["-f", profile, "--", "sh", "-c", script]);
+    wait_instance(&runtime_dir);
+    let pid = rustix::process::Pid::from_child(&sandbox);
+    rustix::process::kill_process(pid, rustix::process::Signal::USR1).unwrap();
+    assert_eq!(sandbox.wait().unwrap().code(), Some(7));
+
+    // SIGTERM ignored, killed after stop_timeout instead of default 30s
+    let script = "trap '' TERM; while :; do sleep 0.1; done";
+    let mut sandbox = spawn_instance(&runtime_dir, &["-f", profile, "--", "sh", "-c", script]);
+    let line = wait_instance(&runtime_dir);
+    let id = line.split('\t').next().unwrap();
+    let started = std::time::Instant::now();
+    let output = instance_command(&runtime_dir, &["stop", id])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(sandbox.wait().unwrap().code(), Some(137));
+    assert!(started.elapsed().as_secs() < 10);
+
+    std::fs::remove_dir_all(runtime_dir).unwrap();
+}
+
+#[test]
+fn test_pty() {
+    // Opening /dev/tty needs a controlling terminal, bwrap --new-session would drop it
+    let script = "test -t 0 && test -t 1 && test -t 2 && exec </dev/tty && echo in-pty";
+    let args = [
+        "--pty",
+        "-f",
+        "./profiles/bwrap-no-home.toml",
+        "--",
+        "sh",
+        "-c",
+        script,
+    ];
+    let output = cargo_command()
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // Relayed from pseudoterminal to plain pipe
+    assert!(output.stdout_str().contains("in-pty"));
+}
+
+#[test]
+fn test_output() {
+    let state_dir = std::env::temp_dir().join(format!("output-{}", rand_id(8)));
+    let run = |script: &str| {
+        let args = [
+            "-f",
+            "./profiles/with-output.toml",
+            "--",
+            "sh",
+            "-c",
+            script,
+        ];
+        cargo_command()
+            .args(args)
+            .env("XDG_STATE_HOME", &state_dir)
+            .output()
+            .unwrap()
+    };
+
+    let output = run("echo to-stdout; echo to-stderr >&2");
+    assert!(output.status.success());
+    // Tee keeps terminal output
+    assert!(output.stdout_str().contains("to-stdout"));
+    let logs = state_dir.join("bwsandbox/logs/with-output");
+    let log = std::fs::read_to_string(logs.join("app.log")).unwrap();
+    let lines: Vec<_> = log.lines().collect();
+    assert_eq!(lines.len(), 2, "{log}");
+    // Separate pipes, order between stdout and stderr is not kept
+    for text in ["to-stdout", "to-stderr"] {
+        assert!(
+            lines.iter().any(|v| v.ends_with(&format!("Z {text}"))),
+            "{log}"
+        );
+    }
+
+    // Rotated by size, max_size is 100 bytes
+    assert!(run("echo second").status.success());
+    assert!(logs.join("app.log.1").exists());
+    assert!(!logs.join("app.log.2").exists());
+
+    std::fs::remove_dir_all(state_dir).unwrap();
+}
+
+#[test]
+fn test_hooks() {
+    let out = std::env::temp_dir().join(format!("hooks-{}", rand_id(8)));
+    let args = [
+        "-f",
+        "./profiles/with-hooks.toml",
+        "--",
+        "sh",
+        "-c",
+        "exit 5",
+    ];
+    let output = cargo_command()
+        .args(args)
+        .env("HOOK_OUT", &out)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(5));
+
+    let log = std::fs::read_to_string(&out).unwrap();
+    let lines: Vec<_> = log.lines().collect();
+    assert_eq!(lines.len(), 3, "{log}");
+    let id = lines[0].strip_prefix("pre_spawn ").unwrap();
+    assert!(!id.is_empty());
+    assert_eq!(lines[1], format!("post_start {id}"));
+    assert_eq!(lines[2], "post_exit 5");
+
+    std::fs::remove_file(out).unwrap();
+}
+
+#[test]
+fn test_process() {
+    let dir = std::env::temp_dir().join(format!("process-{}", rand_id(8)));
+    std::fs::create_dir_all(&dir).unwrap();
+    let out = dir.join("out");
+    let report = dir.join("report.json");
+    let profile = "./profiles/with-process.toml";
+
+    // Placeholders are resolved only on start
+    let output = cargo_command()
+        .args(["--dry-run", "-f", profile, "--", "true"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout_str().contains("{child_pid}"));
+
+    let report_arg = report.to_str().unwrap();
+    let args = ["--exit-report", report_arg, "-f", profile, "--", "true"];
+    let output = cargo_command()
+        .args(args)
+        .env("PROCESS_OUT", &out)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", output.stderr_str());
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&report).unwrap()).unwrap();
+    let child_pid = report["child_pid"].as_u64().unwrap();
+    let written = std::fs::read_to_string(&out).unwrap();
+    assert_eq!(written.trim(), child_pid.to_string());
+    let services = report["usage"]["services"].as_array().unwrap();
+    assert_eq!(services.len(), 3);
+    assert_eq!(services[0]["name"], "process file");
+
+    // Instance selected by name, service names must stay unique
+    let args = [
+        "--set-service",
+        "process.fd.name=file",
+        "-f",
+        profile,
+        "--",
+        "true",
+    ];
+    let output = cargo_command().args(args).output().unwrap();
+    assert!(!output.status.success());
+    assert!(output.stderr_str().contains("used twice"));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_order() {
+    let dir = std::env::temp_dir().join(format!("order-{}", rand_id(8)));
+    std::fs::create_dir_all(&dir).unwrap();
+    let out = dir.join("out");
+    let profile = "./profiles/with-order.toml";
+
+    let output = cargo_command()
+        .args(["--dry-run=json", "-f", profile, "--", "true"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", output.stderr_str());
+    let dump: serde_json::Value = serde_json::from_str(output.stdout_str()).unwrap();
+    let names: Vec<_> = dump["services"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|v| v["name"].as_str())
+        .collect();
+    assert_eq!(names, ["process early", "process late", "process other"]);
+
+    let output = cargo_command()
+        .args(["-f", profile, "--", "true"])
+        .env("ORDER_OUT", &out)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", output.stderr_str());
+    assert_eq!(std::fs::read_to_string(&out).unwrap().trim(), "ok");
+
+    let cycle = r#"process.early.after=["process.late"]"#;
+    let args = ["--set-service", cycle, "-f", profile, "--", "true"];
+    let output = cargo_command().args(args).output().unwrap();
+    assert!(!output.status.success());
+    assert!(
+        output.stderr_str().contains("cycle"),
+        "{}",
+        output.stderr_str()
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_extends() {
+    let args = vec![
+        "--dry-run=json",
+        "-f",
+        "./profiles/with-extends.toml",
+        "--",
+        "printenv",
+    ];
+    let output = cargo_spawn_out(args).unwrap();
+    assert!(output.status.success());
+
+    let dump: serde_json::Value = serde_json::from_str(output.stdout_str()).unwrap();
+    let bwrap_args = dump["bwrap"]["args"].as_array().unwrap();
+    let bwrap_args = bwrap_args
+        .iter()
+        .filter_map(|v| v.as_str())
+        .collect::<Vec<_>>();
+
+    // Parent inline args first, then own args, template rendered once
+    let home = bwrap_args
+        .windows(2)
+        .position(|v| v == ["--tmpfs", "/home"]);
+    let opt = bwrap_args.windows(2).position(|v| v == ["--tmpfs", "/opt"]);
+    assert!(home.unwrap() < opt.unwrap());
+    assert_eq!(
+        bwrap_args.iter().filter(|v| **v == "--new-session").count(),
+        1
+    );
+
+    // Removed service
+    assert!(!bwrap_args.contains(&"APPIMAGE_EXTRACT_AND_RUN"));
+}
+
+#[test]
+fn test_overrides() {
+    let args = vec![
+        "--dry-run=json",
+        "-f",
+        "./profiles/with-appimage.toml",
+        "--set-service",
+        "appimage.extract_and_run=false",
+        "--",
+        "printenv",
+    ];
+    let output = cargo_spawn_out(args).unwrap();
+    assert!(output.status.success());
+    assert!(!output.stdout_str().contains("APPIMAGE_EXTRACT_AND_RUN"));
+
+    // Service missing in profile
+    let args = vec![
+        "--dry-run",
+        "-f",
+        "./profiles/with-appimage.toml",
+        "--set-service",
+        "dbus.user_bus=/bus",
+        "--",
+        "printenv",
+    ];
+    let output = cargo_spawn_out(args).unwrap();
+    assert!(!output.status.success());
+
+    // Include with env var is inlined, so override reaches it
+    let includes = utils::working_dir().join("profiles/includes");
+    let args = [
+        "--dry-run=json",
+        "-f",
+        "./profiles/with-env-include.toml",
+        "--set-service",
+        "env_mapper.unset_all=true",
+        "--",
+        "printenv",
+    ];
+    let output = cargo_command()
+        .args(args)
+        .env("BWSANDBOX_TEST_INCLUDES", includes)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", output.stderr_str());
+    assert!(output.stdout_str().contains("--clearenv"));
+}
+
+#[test]
+fn test_search_path() {
+    let root = std::env::temp_dir().join(format!("search-path-{}", rand_id(8)));
+    let user_dir = root.join("user");
+    let system_dir = root.join("system");
+    let templates = utils::working_dir().join("profiles/templates");
+    let profile = format!(
+        "[bwrap.template]\nname = \"base.j2\"\ndir = \"{}\"\n",
+        templates.display()
+    );
+
+    for dir in [&user_dir, &system_dir] {
+        std::fs::create_dir_all(dir.join("bwsandbox")).unwrap();
+        std::fs::write(dir.join("bwsandbox/shared.toml"), &profile).unwrap();
+    }
+    std::fs::write(system_dir.join("bwsandbox/system-only.toml"), &profile).unwrap();
+
+    let run_cmd = |args: &[&str]| {
+        cargo_command()
+            .args(args)
+            .env("XDG_CONFIG_HOME", &user_dir)
+            .env("XDG_CONFIG_DIRS", &system_dir)
+            .output()
+            .unwrap()
+    };
+
+    let output = run_cmd(&["list-profiles"]);
+    assert!(output.status.success());
+    // System wide dirs of the host may have own profiles
+    let root_str = root.to_str().unwrap();
+    let lines = output
+        .stdout_str()
+        .lines()
+        .filter(|v| v.contains(root_str))
+        .collect::<Vec<_>>();
+    let user_shared = user_dir.join("bwsandbox/shared.toml");
+    let system_shared = system_dir.join("bwsandbox/shared.toml");
+    let system_only = system_dir.join("bwsandbox/system-only.toml");
+    assert_eq!(lines[0], format!("shared\t{}", user_shared.display()));
+    assert_eq!(
+        lines[1],
+        format!("shared\t{} (shadowed)", system_shared.display())
+    );
+    assert_eq!(lines[2], format!("system-only\t{}", system_only.display()));
+
+    // Profile from system dir
+    let output = run_cmd(&["--dry-run", "-n", "system-only", "--", "ls"]);
+    assert!(output.status.success());
+
+    let output = run_cmd(&["--dry-run", "-n", "missing", "--", "ls"]);
+    assert!(output.stderr_str().contains("\"missing.toml\" not found"));
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_config_auto() {
+    let root = std::env::temp_dir().join(format!("config-auto-{}", rand_id(8)));
+    let config_dir = root.join("bwsandbox");
+    let templates = utils::working_dir().join("profiles/templates");
+    std::fs::create_dir_all(&config_dir).unwrap();
+
+    let profile = format!(
+        "[bwrap.template]\nname = \"base.j2\"\ndir = \"{}\"\n",
+        templates.display()
+    );
+    let with_appimage = format!("{profile}\n[appimage]\ntype = \"inline\"\n");
+    std::fs::write(config_dir.join("generic.toml"), &profile).unwrap();
+    std::fs::write(config_dir.join("listing.toml"), &with_appimage).unwrap();
+    let rules = "default = \"generic\"\n\n[[rules]]\nprofile = \"listing\"\nbasename = \"ls\"\nargs = \"^-la$\"\n";
+    std::fs::write(config_dir.join("auto.toml"), rules).unwrap();
+
+    let run_cmd = |args: &[&str]| {
+        cargo_command()
+            .args(args)
+            .env("XDG_CONFIG_HOME", &root)
+            .output()
+            .unwrap()
+    };
+
+    // Rule matched
+    let output = run_cmd(&["--dry-run", "-a", "--", "/bin/ls", "-la"]);
+    assert!(output.status.success());
+    assert!(output.stdout_str().contains("APPIMAGE_EXTRACT_AND_RUN"));
+
+    // Args mismatch, default profile used
+    let output = run_cmd(&["--dry-run", "-a", "--", "/bin/ls"]);
+    assert!(output.status.success());
+    assert!(!output.stdout_str().contains("APPIMAGE_EXTRACT_AND_RUN"));
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_mounts() {
+    let args = vec![
+        "--dry-run=json",
+        "-f",
+        "./profiles/with-mounts.toml",
+        "--",
+        "cat",
+        "/home/hello.txt",
+    ];
+    let output = cargo_spawn_out(args).unwrap();
+    assert!(output.status.success());
+
+    let dump: serde_json::Value = serde_json::from_str(output.stdout_str()).unwrap();
+    let bwrap_args = dump["bwrap"]["args"].as_array().unwrap();
+    let bwrap_args = bwrap_args
+        .iter()
+        .filter_map(|v| v.as_str())
+        .collect::<Vec<_>>();
+
+    // Mounts follow template args
+    let tmpfs = ["--perms", "0700", "--size", "1048576", "--tmpfs", "/home"];
+    let tmpfs = bwrap_args.windows(tmpfs.len()).position(|v| v == tmpfs);
+    let usr = bwrap_args
+        .windows(3)
+        .position(|v| v[0] == "--ro-bind" && v[1] == "/usr");
+    assert!(usr.unwrap() < tmpfs.unwrap());
+
+    // Relative source resolved against profile dir
+    let templates = utils::working_dir().join("profiles/templates");
+    let templates = templates.to_str().unwrap();
+    assert!(
+        bwrap_args
+            .windows(3)
+            .any(|v| v == ["--ro-bind", templates, "/home/templates"])
+    );
+
+    assert!(!bwrap_args.contains(&"/home/missing"));
+    assert!(bwrap_args.contains(&"/home/hello.txt"));
+}
+
+#[test]
+fn test_lint() {
+    let args = vec!["--dry-run", "-f", "./profiles/with-lint.toml", "--", "ls"];
+    let output = cargo_spawn_out(args).unwrap();
+    assert!(!output.status.success());
+    assert!(output.stderr_str().contains("[shadowed_mount]"));
+
+    let args = vec![
+        "--dry-run",
+        "-f",
+        "./profiles/with-lint.toml",
+        "--set-service",
+        "bwrap.lint.shadowed_mount=off",
+        "--",
+        "ls",
+    ];
+    let output = cargo_spawn_out(args).unwrap();
+    assert!(output.status.success());
+}
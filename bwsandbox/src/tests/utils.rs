@@ -1,7 +1,8 @@
 use std::{
     ffi::OsStr,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Child, Command, Output, Stdio},
+    time::{Duration, Instant},
 };
 
 pub trait OutputExtra {
@@ -51,3 +52,40 @@ pub(super) fn working_dir() -> PathBuf {
 
     root_dir.join(this_dir)
 }
+
+// Separate runtime dir keeps instances of parallel tests apart
+pub(super) fn runtime_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("{name}-{}", crate::utils::rand_id(8)));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub(super) fn instance_command(runtime_dir: &Path, args: &[&str]) -> Command {
+    let mut command = cargo_command();
+    command.args(args).env("XDG_RUNTIME_DIR", runtime_dir);
+    command
+}
+
+pub(super) fn spawn_instance(runtime_dir: &Path, args: &[&str]) -> Child {
+    instance_command(runtime_dir, args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap()
+}
+
+// `ps` line of the first registered instance
+pub(super) fn wait_instance(runtime_dir: &Path) -> String {
+    let started = Instant::now();
+    loop {
+        let output = instance_command(runtime_dir, &["ps"]).output().unwrap();
+        if let Some(line) = output.stdout_str().lines().next() {
+            return line.to_owned();
+        }
+        assert!(
+            started.elapsed().as_secs() < 30,
+            "Instance is not registered"
+        );
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
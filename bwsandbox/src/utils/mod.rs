@@ -6,6 +6,7 @@ use std::{
 };
 
 pub use path_bin::which_bin;
+pub use rand::is_rand_id;
 #[cfg(test)]
 pub use rand::rand_id;
 
@@ -25,21 +26,41 @@ pub fn sandbox_id() -> &'static str {
     PREFIX.get_or_init(|| rand::rand_id(16))
 }
 
-pub fn temp_dir() -> &'static Path {
+pub fn runtime_dir() -> &'static Path {
     const DIRS: &[&str] = &["RUNTIME_DIRECTORY", "XDG_RUNTIME_DIR"];
 
-    static TEMP_DIR: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
-    TEMP_DIR.get_or_init(|| {
+    static RUNTIME_DIR: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+    RUNTIME_DIR.get_or_init(|| {
         let base = DIRS.iter().find_map(|v| std::env::var(v).ok());
         let base = base.unwrap_or_else(|| {
             log::warn!("Can't find any temp dir in {DIRS:?}, using std::env::temp_dir");
             std::env::temp_dir().to_string_lossy().into()
         });
         assert!(!base.is_empty());
-        PathBuf::from(base).join(format!("{APP_NAME}-workdir-{}", sandbox_id()))
+        PathBuf::from(base)
     })
 }
 
+pub fn temp_dir() -> &'static Path {
+    static TEMP_DIR: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+    TEMP_DIR.get_or_init(|| runtime_dir().join(format!("{APP_NAME}-workdir-{}", sandbox_id())))
+}
+
+// Persistent per user data, f.e. logs
+pub fn state_dir() -> Result<PathBuf, crate::error::AppError> {
+    const STATE_DIR: &str = "XDG_STATE_HOME";
+    const HOME: &str = "HOME";
+
+    let base = match std::env::var_os(STATE_DIR) {
+        Some(v) if !v.is_empty() => PathBuf::from(v),
+        _ => {
+            let home = std::env::var(HOME).map_err(crate::error::AppError::env(HOME))?;
+            PathBuf::from(home).join(".local/state")
+        }
+    };
+    Ok(base.join(APP_NAME))
+}
+
 pub fn deserialize<T: serde::de::DeserializeOwned>(s: &str) -> Result<T, toml::de::Error> {
     toml::from_str(s)
 }
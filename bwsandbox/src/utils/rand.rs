@@ -12,6 +12,11 @@ pub fn rand_id(len: usize) -> String {
     String::from_utf8(bytes).expect("Alphabet must be utf8 compatible")
 }
 
+// Ids come from user for commands like `stop`, so never used as paths without check
+pub fn is_rand_id(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|v| RAND_ALPHABET.contains(&v))
+}
+
 #[test]
 fn test_rand_id() {
     let size = 32;
@@ -21,4 +26,7 @@ fn test_rand_id() {
     for ch in id.chars() {
         assert!(RAND_ALPHABET.contains(&(ch as u8)));
     }
+    assert!(is_rand_id(&id));
+    assert!(!is_rand_id("../id"));
+    assert!(!is_rand_id(""));
 }